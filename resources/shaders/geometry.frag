@@ -1,7 +1,9 @@
 #version 100
 
-//in vec4 v_color;
+precision mediump float;
+
+varying vec4 v_color;
 
 void main() {
-    gl_FragColor = vec4(1.0, 0.0, 0.0, 1.0);
+    gl_FragColor = v_color;
 }
\ No newline at end of file
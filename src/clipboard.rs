@@ -0,0 +1,44 @@
+use copypasta::{ClipboardContext, ClipboardProvider};
+use imgui::ClipboardBackend;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{components::SavedComponent, document::{self, DOCUMENT_VERSION}};
+
+pub struct SystemClipboard(ClipboardContext);
+
+impl SystemClipboard {
+    pub fn new() -> Option<Self> {
+        ClipboardContext::new().ok().map(Self)
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.0.get_contents().ok()
+    }
+
+    fn set(&mut self, value: &str) {
+        if let Err(error) = self.0.set_contents(value.to_string()) {
+            eprintln!("Failed to set clipboard: {}", error);
+        }
+    }
+}
+
+// Same shape as a document's components, so clipboard text from older builds goes through the same migrations
+#[derive(Serialize, Deserialize)]
+struct Clipboard {
+    version: u32,
+    components: Vec<SavedComponent>,
+}
+
+pub fn to_text(components: &[SavedComponent]) -> String {
+    let clipboard = Clipboard { version: DOCUMENT_VERSION, components: components.to_vec() };
+    serde_json::to_string(&clipboard).expect("Failed to serialise clipboard")
+}
+
+pub fn from_text(text: &str) -> Option<Vec<SavedComponent>> {
+    let mut clipboard: Value = serde_json::from_str(text).ok()?;
+    document::migrate(&mut clipboard).ok()?;
+    serde_json::from_value::<Clipboard>(clipboard).ok().map(|clipboard| clipboard.components)
+}
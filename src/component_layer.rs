@@ -1,64 +1,1232 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc};
 
 use imgui::Ui;
-use nalgebra_glm::Vec2;
+use nalgebra_glm::{Vec2, vec2};
 
-use crate::{triangle_renderer::TriangleRenderer, components::{Component, ComponentType}, state::State, placer::Placer};
+use crate::{triangle_renderer::TriangleRenderer, components::{Component, ComponentId, ComponentType, LayerId, SavedComponent, Binding, PRECISE_TOLERANCE, merge_bounds, vertices_intersect_rect, vertices_within_rect, group::Group, style::Style, text::Label, transform::Transform}, state::State, placer::Placer, history::{History, Command}, layers::Layer, spatial_index::SpatialIndex, clipboard};
+
+const HANDLE_SIZE: f32 = 4.0;
+const HANDLE_FILL: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const HANDLE_BORDER: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const HANDLE_LINE: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+const MARQUEE_FILL: [f32; 4] = [0.3, 0.5, 0.9, 0.2];
+const MARQUEE_BORDER: [f32; 4] = [0.3, 0.5, 0.9, 1.0];
+const OPEN_GROUP_BORDER: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const DUPLICATE_OFFSET: Vec2 = Vec2::new(20.0, -20.0);
+const SNAP_TOLERANCE: f32 = 8.0;
+const SNAP_INDICATOR_SIZE: f32 = 6.0;
+const SNAP_INDICATOR_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+// Pixels between the top of the selection and its rotation handle
+const ROTATION_HANDLE_OFFSET: f32 = 24.0;
+const ROTATION_SNAP_ANGLE: f32 = std::f32::consts::PI / 12.0;
+// Right-clicks that move further than this pan the view instead of opening the context menu
+const CONTEXT_MENU_DRAG_THRESHOLD: f32 = 4.0;
+pub const CONTEXT_MENU: &str = "context menu";
+
+#[derive(Clone, Copy)]
+pub enum Align {
+    Left,
+    HorizontalCenter,
+    Right,
+    Top,
+    VerticalCenter,
+    Bottom,
+}
+
+#[derive(Clone, Copy)]
+pub enum Distribute {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Clone, Copy)]
+pub enum ZOrder {
+    Forward,
+    Backward,
+    Front,
+    Back,
+}
+
+struct Drag {
+    handle: Option<usize>,
+    start_mouse_pos: Vec2,
+    // Moves snap this point of the grabbed component, rather than the mouse, to the grid
+    anchor: Vec2,
+    moved: Vec2,
+    snap_target: Option<Vec2>,
+    coalesce: bool,
+}
+
+struct Rotation {
+    center: Vec2,
+    start_angle: f32,
+    angle: f32,
+    coalesce: bool,
+}
+
+struct Marquee {
+    start: Vec2,
+    end: Vec2,
+    // Shift-dragging adds to whatever was selected before the marquee started
+    initial_selection: Vec<Rc<RefCell<dyn Component>>>,
+}
 
 pub struct ComponentLayer {
+    // In draw order within each layer; components of different layers can be interleaved
     primitives: Vec<Rc<RefCell<dyn Component>>>,
+    // Bounds of every primitive by id, whatever its layer
+    index: SpatialIndex,
+    // Where each primitive is in `primitives` by id, so the index's results can be put in draw order
+    positions: HashMap<ComponentId, usize>,
+    // Bottom first
+    layers: Vec<Layer>,
+    active_layer: LayerId,
     placer: Option<Placer>,
-    selected: Option<Rc<RefCell<dyn Component>>>,
+    selected: Vec<Rc<RefCell<dyn Component>>>,
+    history: History,
+    drag: Option<Drag>,
+    rotation: Option<Rotation>,
+    marquee: Option<Marquee>,
+    context_press: Option<Vec2>,
+    // Groups opened up in place by double-clicking them, so their children can be edited directly. Outermost first;
+    // only the innermost one's children can be selected
+    open_groups: Vec<ComponentId>,
+    clipboard: Vec<SavedComponent>,
+    next_id: ComponentId,
+    style: Style,
 }
 
 impl ComponentLayer {
     pub fn new() -> Self {
-        Self { primitives: vec![], placer: None, selected: None }
+        Self { primitives: vec![], index: SpatialIndex::new(), positions: HashMap::new(), layers: vec![Layer::new(0, "Layer 1".to_string())], active_layer: 0, placer: None, selected: vec![], history: History::new(), drag: None, rotation: None, marquee: None, context_press: None, open_groups: vec![], clipboard: vec![], next_id: 1, style: Style::default() }
     }
 
     pub fn update_io(&mut self, ui: &Ui, state: &State) {
         if ui.is_key_pressed(imgui::Key::Escape) {
-            self.set_placer(ComponentType::None);
+            if self.has_placer() {
+                self.set_placer(ComponentType::None);
+            } else {
+                self.close_group();
+            }
         }
-    
-        if ui.is_mouse_clicked(imgui::MouseButton::Left) && !ui.is_any_item_hovered() {
-            let mouse_pos = state.window_space_to_world_space(Vec2::from_column_slice(&ui.io().mouse_pos));
-            self.select(mouse_pos);
+
+        if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::Z) && !ui.io().want_text_input {
+            if ui.io().key_shift {
+                self.redo();
+            } else {
+                self.undo();
+            }
+        }
+
+        if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::A) && !ui.io().want_text_input {
+            self.select_all();
         }
+
+        let mouse_pos = state.window_space_to_world_space(Vec2::from_column_slice(&ui.io().mouse_pos));
+        if !ui.io().want_text_input {
+            if ui.is_key_pressed(imgui::Key::Delete) || ui.is_key_pressed(imgui::Key::Backspace) {
+                self.delete_selected();
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::C) {
+                self.copy_selected(ui);
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::X) {
+                self.copy_selected(ui);
+                self.delete_selected();
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::V) {
+                self.paste(ui, mouse_pos);
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::D) {
+                self.duplicate_selected();
+            }
+
+            if ui.is_key_pressed(imgui::Key::Enter) || ui.is_key_pressed(imgui::Key::KeypadEnter) {
+                self.complete_placer(state);
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::G) {
+                if ui.io().key_shift {
+                    self.ungroup_selected();
+                } else {
+                    self.group_selected();
+                }
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::RightBracket) {
+                self.reorder_selected(if ui.io().key_shift { ZOrder::Front } else { ZOrder::Forward });
+            }
+
+            if ui.io().key_ctrl && ui.is_key_pressed(imgui::Key::LeftBracket) {
+                self.reorder_selected(if ui.io().key_shift { ZOrder::Back } else { ZOrder::Backward });
+            }
+        }
+
+        let window_mouse_pos = Vec2::from_column_slice(&ui.io().mouse_pos);
+        if ui.is_mouse_clicked(imgui::MouseButton::Right) && !ui.is_any_item_hovered() && !self.has_placer() {
+            self.context_press = Some(window_mouse_pos);
+        }
+
+        // Right-clicking an unselected component selects it, so the menu acts on what was clicked
+        if ui.is_mouse_released(imgui::MouseButton::Right) {
+            let press = self.context_press.take();
+            if press.is_some_and(|press| (window_mouse_pos - press).magnitude() <= CONTEXT_MENU_DRAG_THRESHOLD) {
+                if let Some(hovered) = self.get_hovered(mouse_pos).filter(|hovered| !self.is_selected(hovered)) {
+                    self.set_selection(vec![hovered]);
+                }
+                ui.open_popup(CONTEXT_MENU);
+            }
+        }
+
+        if ui.is_mouse_clicked(imgui::MouseButton::Left) && !ui.is_any_item_hovered() && !self.has_placer() {
+            self.begin_drag(mouse_pos, state, ui.io().key_shift);
+        }
+
+        if ui.is_mouse_double_clicked(imgui::MouseButton::Left) && !ui.is_any_item_hovered() && !self.has_placer() {
+            self.open_group(mouse_pos);
+        }
+
+        if ui.is_mouse_down(imgui::MouseButton::Left) {
+            self.update_drag(mouse_pos, state);
+            self.update_rotation(mouse_pos, ui.io().key_shift, state);
+            self.update_marquee(mouse_pos, ui.io().key_alt);
+        }
+
+        if ui.is_mouse_released(imgui::MouseButton::Left) {
+            self.drag = None;
+            self.rotation = None;
+            self.marquee = None;
+        }
+
+        self.update_connectors();
     }
 
     pub fn draw(&self, ui: &Ui, state: &State, triangle_renderer: &mut TriangleRenderer) {
-        triangle_renderer.prepare_render(state);
-        for primitive in &self.primitives {
-            triangle_renderer.data(primitive.borrow().get_vertices());
+        let (min, max) = state.get_visible_bounds();
+        for primitive in self.query_drawn(min, max) {
+            triangle_renderer.data(primitive.borrow().get_vertices(state.get_tolerance()));
             triangle_renderer.render();
         }
 
+        let transform = self.get_scope_transform();
+        let mut snap_target = self.drag.as_ref().and_then(|drag| drag.snap_target).map(|target| transform.apply(target));
         if let Some(placer) = &self.placer {
-            let mouse_pos = state.window_space_to_world_space(Vec2::from_column_slice(&ui.io().mouse_pos));
-            for primitive in &placer.get_preview(mouse_pos, state) {
-                triangle_renderer.data(primitive.get_vertices());
+            let (mouse_pos, target) = self.snap(state.window_space_to_world_space(Vec2::from_column_slice(&ui.io().mouse_pos)), state);
+            for primitive in &placer.get_preview(mouse_pos, self.style) {
+                triangle_renderer.data(primitive.get_vertices(state.get_tolerance()));
                 triangle_renderer.render();
             }
+            snap_target = target;
+        }
+
+        let draw_list = ui.get_background_draw_list();
+        if let Some(target) = snap_target {
+            let target = state.world_space_to_window_space(target);
+            let size = SNAP_INDICATOR_SIZE;
+            draw_list.add_circle([target.x, target.y], size, SNAP_INDICATOR_COLOR).build();
+            draw_list.add_line([target.x - size, target.y - size], [target.x + size, target.y + size], SNAP_INDICATOR_COLOR).build();
+            draw_list.add_line([target.x - size, target.y + size], [target.x + size, target.y - size], SNAP_INDICATOR_COLOR).build();
         }
+
+        if let [selected] = self.selected.as_slice() {
+            for (from, to) in selected.borrow().get_handle_lines() {
+                let (from, to) = (state.world_space_to_window_space(transform.apply(from)), state.world_space_to_window_space(transform.apply(to)));
+                draw_list.add_line([from.x, from.y], [to.x, to.y], HANDLE_LINE).build();
+            }
+            for handle in selected.borrow().get_handles() {
+                let handle = state.world_space_to_window_space(transform.apply(handle));
+                let from = [handle.x - HANDLE_SIZE, handle.y - HANDLE_SIZE];
+                let to = [handle.x + HANDLE_SIZE, handle.y + HANDLE_SIZE];
+                draw_list.add_rect(from, to, HANDLE_FILL).filled(true).build();
+                draw_list.add_rect(from, to, HANDLE_BORDER).build();
+            }
+        }
+
+        if let Some((handle, _)) = self.get_rotation_handle(state) {
+            draw_list.add_line([handle.x, handle.y], [handle.x, handle.y + ROTATION_HANDLE_OFFSET], HANDLE_LINE).build();
+            draw_list.add_circle([handle.x, handle.y], HANDLE_SIZE, HANDLE_FILL).filled(true).build();
+            draw_list.add_circle([handle.x, handle.y], HANDLE_SIZE, HANDLE_BORDER).build();
+        }
+
+        if !self.open_groups.is_empty() {
+            if let Some((min, max)) = get_combined_bounds(&self.get_scope()).and_then(|bounds| transform_bounds(&transform, bounds)) {
+                let (from, to) = (state.world_space_to_window_space(min), state.world_space_to_window_space(max));
+                draw_list.add_rect([from.x, from.y], [to.x, to.y], OPEN_GROUP_BORDER).build();
+            }
+        }
+
+        if let Some(marquee) = &self.marquee {
+            let from = state.world_space_to_window_space(marquee.start);
+            let to = state.world_space_to_window_space(marquee.end);
+            draw_list.add_rect([from.x, from.y], [to.x, to.y], MARQUEE_FILL).filled(true).build();
+            draw_list.add_rect([from.x, from.y], [to.x, to.y], MARQUEE_BORDER).build();
+        }
+    }
+
+    fn set_selection(&mut self, selection: Vec<Rc<RefCell<dyn Component>>>) {
+        for selected in &self.selected {
+            selected.borrow_mut().set_highlighted(false);
+        }
+        self.selected = selection;
+        for selected in &self.selected {
+            selected.borrow_mut().set_highlighted(true);
+        }
+    }
+
+    fn is_selected(&self, component: &Rc<RefCell<dyn Component>>) -> bool {
+        self.selected.iter().any(|selected| Rc::ptr_eq(selected, component))
+    }
+
+    // Also drops anything whose layer has since been hidden or locked, and closes open groups that have gone the same way
+    fn deselect_removed(&mut self) {
+        self.open_groups.truncate(self.get_open_groups().len());
+        let selectable = self.get_selectable();
+        let selection = self.selected.iter()
+            .filter(|selected| selectable.iter().any(|primitive| Rc::ptr_eq(primitive, selected)))
+            .cloned()
+            .collect();
+        self.set_selection(selection);
+    }
+
+    // Primitives whose bounds touch the rectangle, in the same order as `get_drawn`
+    fn query_drawn(&self, min: Vec2, max: Vec2) -> Vec<&Rc<RefCell<dyn Component>>> {
+        self.get_in_draw_order(self.index.query(min, max))
+    }
+
+    // The primitives with these IDs that are drawn, in the same order as `get_drawn`
+    fn get_in_draw_order(&self, ids: HashSet<ComponentId>) -> Vec<&Rc<RefCell<dyn Component>>> {
+        let layers: HashMap<LayerId, usize> = self.layers.iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.visible)
+            .map(|(order, layer)| (layer.id, order))
+            .collect();
+        let mut found: Vec<(usize, usize)> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let index = *self.positions.get(&id)?;
+                Some((*layers.get(&self.primitives[index].borrow().get_layer())?, index))
+            })
+            .collect();
+        found.sort_unstable();
+        found.into_iter().map(|(_, index)| &self.primitives[index]).collect()
+    }
+
+    // Bottom layer first, skipping hidden layers
+    fn get_drawn(&self) -> Vec<&Rc<RefCell<dyn Component>>> {
+        self.layers.iter()
+            .filter(|layer| layer.visible)
+            .flat_map(|layer| self.primitives.iter().filter(|primitive| primitive.borrow().get_layer() == layer.id))
+            .collect()
+    }
+
+    fn is_editable(&self, layer: LayerId) -> bool {
+        self.layers.iter().any(|other| other.id == layer && other.visible && !other.locked)
+    }
+
+    // The innermost open group's children, or else everything on visible, unlocked layers
+    fn get_selectable(&self) -> Vec<Rc<RefCell<dyn Component>>> {
+        if let Some((_, group)) = self.get_open_groups().pop() {
+            return group.borrow().get_children().to_vec();
+        }
+        self.get_drawn()
+            .into_iter()
+            .filter(|primitive| self.is_editable(primitive.borrow().get_layer()))
+            .cloned()
+            .collect()
+    }
+
+    // Each open group with its index among its parent's children, outermost first. The chain ends at a group that is
+    // no longer there, or whose layer is hidden or locked
+    fn get_open_groups(&self) -> Vec<(usize, Rc<RefCell<dyn Component>>)> {
+        let mut open_groups: Vec<(usize, Rc<RefCell<dyn Component>>)> = vec![];
+        for id in &self.open_groups {
+            let parent = open_groups.last().map(|(_, group)| group.clone());
+            let found = match &parent {
+                Some(parent) => find_child(parent.borrow().get_children(), *id),
+                None => self.positions.get(id)
+                    .map(|index| (*index, self.primitives[*index].clone()))
+                    .filter(|(_, group)| self.is_editable(group.borrow().get_layer())),
+            };
+            let Some(found) = found else {
+                break;
+            };
+            open_groups.push(found);
+        }
+        open_groups
+    }
+
+    // What edits apply to, and what their indices refer to: the innermost open group's children, or else every component
+    fn get_scope(&self) -> Vec<Rc<RefCell<dyn Component>>> {
+        match self.get_open_groups().pop() {
+            Some((_, group)) => group.borrow().get_children().to_vec(),
+            None => self.primitives.clone(),
+        }
+    }
+
+    // From the innermost open group's coordinates to world space
+    fn get_scope_transform(&self) -> Transform {
+        self.get_open_groups()
+            .iter()
+            .fold(Transform::default(), |transform, (_, group)| group.borrow().get_transform().then(&transform))
+    }
+
+    // Makes a command on the scope's indices apply from the top level, through every open group
+    fn nest(&self, command: Command) -> Command {
+        self.get_open_groups()
+            .into_iter()
+            .rev()
+            .fold(command, |command, (index, group)| Command::Nested { index, id: group.borrow().get_id(), command: Box::new(command) })
+    }
+
+    // Indices into the scope, in draw order, so commands built from them can be replayed front to back
+    fn get_selected_indices(&self) -> Vec<usize> {
+        self.get_scope()
+            .iter()
+            .enumerate()
+            .filter(|(_, primitive)| self.is_selected(primitive))
+            .map(|(index, _)| index)
+            .collect()
     }
 
-    fn set_selected_highlighted(&self, highlighted: bool) {
-        if let Some(selected) = self.selected.as_ref() {
-            selected.borrow_mut().set_highlighted(highlighted);
+    fn get_hovered(&self, mouse_pos: Vec2) -> Option<Rc<RefCell<dyn Component>>> {
+        if !self.open_groups.is_empty() {
+            let mouse_pos = self.get_scope_transform().invert(mouse_pos);
+            return self.get_selectable().into_iter().rev().find(|child| child.borrow().hitbox_intersects(mouse_pos));
         }
+
+        self.query_drawn(mouse_pos, mouse_pos)
+            .into_iter()
+            .rev()
+            .filter(|primitive| self.is_editable(primitive.borrow().get_layer()))
+            .find(|primitive| primitive.borrow().hitbox_intersects(mouse_pos))
+            .cloned()
     }
 
-    pub fn select(&mut self, mouse_pos: Vec2) {
-        self.set_selected_highlighted(false);
-        self.selected = self.primitives.iter().rev().find(|primitive| primitive.borrow().hitbox_intersects(mouse_pos)).cloned();
-        self.set_selected_highlighted(true);
+    fn get_hovered_handle(&self, mouse_pos: Vec2, state: &State) -> Option<usize> {
+        let [selected] = self.selected.as_slice() else {
+            return None;
+        };
+        let tolerance = state.window_space_to_world_space_distance(HANDLE_SIZE);
+        let transform = self.get_scope_transform();
+        selected.borrow()
+            .get_handles()
+            .iter()
+            .position(|handle| (transform.apply(*handle) - mouse_pos).abs().max() <= tolerance)
     }
 
+    // In window space, above the selection's bounds, along with the centre of the bounds that rotation turns about in
+    // the scope's coordinates
+    fn get_rotation_handle(&self, state: &State) -> Option<(Vec2, Vec2)> {
+        if self.has_placer() {
+            return None;
+        }
+
+        let (min, max) = self.get_bounds(true)?;
+        let top = state.world_space_to_window_space(vec2((min.x + max.x) / 2.0, max.y));
+        Some((top - vec2(0.0, ROTATION_HANDLE_OFFSET), self.get_scope_transform().invert((min + max) / 2.0)))
+    }
+
+    fn begin_drag(&mut self, mouse_pos: Vec2, state: &State, toggle: bool) {
+        if !toggle {
+            if let Some((handle, center)) = self.get_rotation_handle(state) {
+                if (state.world_space_to_window_space(mouse_pos) - handle).magnitude() <= HANDLE_SIZE {
+                    let offset = self.get_scope_transform().invert(mouse_pos) - center;
+                    self.rotation = Some(Rotation { center, start_angle: offset.y.atan2(offset.x), angle: 0.0, coalesce: false });
+                    return;
+                }
+            }
+
+            if let Some(handle) = self.get_hovered_handle(mouse_pos, state) {
+                let anchor = self.selected[0].borrow().get_handles()[handle];
+                let start_mouse_pos = self.get_scope_transform().invert(mouse_pos);
+                self.drag = Some(Drag { handle: Some(handle), start_mouse_pos, anchor, moved: vec2(0.0, 0.0), snap_target: None, coalesce: false });
+                return;
+            }
+        }
+
+        self.close_groups_outside(mouse_pos);
+        match (self.get_hovered(mouse_pos), toggle) {
+            (Some(hovered), true) => {
+                let mut selection = self.selected.clone();
+                if self.is_selected(&hovered) {
+                    selection.retain(|selected| !Rc::ptr_eq(selected, &hovered));
+                } else {
+                    selection.push(hovered);
+                }
+                self.set_selection(selection);
+            }
+            (Some(hovered), false) => {
+                if !self.is_selected(&hovered) {
+                    self.set_selection(vec![hovered.clone()]);
+                }
+                let anchor = hovered.borrow().get_handles()[0];
+                let start_mouse_pos = self.get_scope_transform().invert(mouse_pos);
+                self.drag = Some(Drag { handle: None, start_mouse_pos, anchor, moved: vec2(0.0, 0.0), snap_target: None, coalesce: false });
+            }
+            (None, additive) => {
+                let initial_selection = if additive { self.selected.clone() } else { vec![] };
+                self.set_selection(initial_selection.clone());
+                self.marquee = Some(Marquee { start: mouse_pos, end: mouse_pos, initial_selection });
+            }
+        }
+    }
+
+    // Returns the offset that moves the closest of `points` onto another component's snap point, and that snap point,
+    // all in the scope's coordinates. Inside an open group only its children are snapped to
+    fn find_snap(&self, points: &[Vec2], state: &State) -> Option<(Vec2, Vec2)> {
+        if !state.is_object_snapping() {
+            return None;
+        }
+
+        let tolerance = self.get_scope_transform().get_local_tolerance(state.window_space_to_world_space_distance(SNAP_TOLERANCE));
+        let targets = match self.open_groups.is_empty() {
+            // Snap points lie within their component's bounds, so only components this close can have one in reach
+            true => {
+                let reach = vec2(tolerance, tolerance);
+                let nearby = points.iter().flat_map(|point| self.index.query(point - reach, point + reach)).collect();
+                self.get_in_draw_order(nearby).into_iter().cloned().collect()
+            }
+            false => self.get_selectable(),
+        };
+        targets.iter()
+            .filter(|primitive| !(self.drag.is_some() && self.is_selected(primitive)))
+            .flat_map(|primitive| primitive.borrow().get_snap_points())
+            .flat_map(|target| points.iter().map(move |point| (target - point, target)))
+            .filter(|(offset, _)| offset.magnitude() <= tolerance)
+            .min_by(|(offset1, _), (offset2, _)| offset1.magnitude().total_cmp(&offset2.magnitude()))
+    }
+
+    // Object snapping takes priority over the grid
+    fn snap(&self, point: Vec2, state: &State) -> (Vec2, Option<Vec2>) {
+        match self.find_snap(&[point], state) {
+            Some((offset, target)) => (point + offset, Some(target)),
+            None => (self.snap_to_grid(point, state), None),
+        }
+    }
+
+    // The grid lies in world space, whatever group is open
+    fn snap_to_grid(&self, point: Vec2, state: &State) -> Vec2 {
+        let transform = self.get_scope_transform();
+        transform.invert(state.snap(transform.apply(point)))
+    }
+
+    fn update_drag(&mut self, mouse_pos: Vec2, state: &State) {
+        let indices = self.get_selected_indices();
+        let Some(drag) = self.drag.as_ref() else {
+            return;
+        };
+        let scope = self.get_scope();
+        let mouse_pos = self.get_scope_transform().invert(mouse_pos);
+        let unsnapped = mouse_pos - drag.start_mouse_pos;
+        let points: Vec<Vec2> = match drag.handle {
+            Some(_) => vec![drag.anchor + unsnapped],
+            None => indices.iter()
+                .flat_map(|index| scope[*index].borrow().get_snap_points())
+                .map(|point| point - drag.moved + unsnapped)
+                .collect(),
+        };
+        let (moved, snap_target) = match self.find_snap(&points, state) {
+            Some((offset, target)) => (unsnapped + offset, Some(target)),
+            None => (self.snap_to_grid(drag.anchor + unsnapped, state) - drag.anchor, None),
+        };
+
+        let drag = self.drag.as_mut().unwrap();
+        drag.snap_target = snap_target;
+        if indices.is_empty() || moved == drag.moved {
+            return;
+        }
+
+        let befores: Vec<_> = indices.iter().map(|index| Box::new(scope[*index].borrow().save())).collect();
+        for index in &indices {
+            let mut primitive = scope[*index].borrow_mut();
+            match drag.handle {
+                Some(handle) => primitive.move_handle(handle, drag.anchor + moved),
+                None => primitive.translate(moved - drag.moved),
+            }
+        }
+        drag.moved = moved;
+        let coalesce = drag.coalesce;
+        drag.coalesce = true;
+
+        // Moved connectors attach to whatever outlines their endpoints are now near, and detach from the rest
+        for index in &indices {
+            self.bind_endpoints(&scope[*index], state);
+        }
+        let commands = indices.into_iter()
+            .zip(befores)
+            .map(|(index, before)| Command::Modify { index, before, after: Box::new(scope[index].borrow().save()) })
+            .collect();
+        self.record(self.nest(Command::Batch(commands)), coalesce);
+    }
+
+    // Holding shift snaps to multiples of 15 degrees
+    fn update_rotation(&mut self, mouse_pos: Vec2, snap: bool, state: &State) {
+        let indices = self.get_selected_indices();
+        let scope = self.get_scope();
+        let mouse_pos = self.get_scope_transform().invert(mouse_pos);
+        let Some(rotation) = self.rotation.as_mut() else {
+            return;
+        };
+        let offset = mouse_pos - rotation.center;
+        let mut angle = offset.y.atan2(offset.x) - rotation.start_angle;
+        if snap {
+            angle = (angle / ROTATION_SNAP_ANGLE).round() * ROTATION_SNAP_ANGLE;
+        }
+        if angle == rotation.angle {
+            return;
+        }
+
+        let befores: Vec<_> = indices.iter().map(|index| Box::new(scope[*index].borrow().save())).collect();
+        for index in &indices {
+            let mut primitive = scope[*index].borrow_mut();
+            let mut transform = primitive.get_transform();
+            transform.rotate_about(angle - rotation.angle, rotation.center);
+            primitive.set_transform(transform);
+        }
+        rotation.angle = angle;
+        let coalesce = rotation.coalesce;
+        rotation.coalesce = true;
+
+        for index in &indices {
+            self.bind_endpoints(&scope[*index], state);
+        }
+        let commands = indices.into_iter()
+            .zip(befores)
+            .map(|(index, before)| Command::Modify { index, before, after: Box::new(scope[index].borrow().save()) })
+            .collect();
+        self.record(self.nest(Command::Batch(commands)), coalesce);
+    }
+
+    // Connectors bind to the topmost other component in the same scope whose outline is within a few pixels, whether or
+    // not object snapping is on
+    fn find_binding(&self, point: Vec2, exclude: ComponentId, state: &State) -> Option<Binding> {
+        let tolerance = self.get_scope_transform().get_local_tolerance(state.window_space_to_world_space_distance(SNAP_TOLERANCE));
+        self.get_scope().iter().rev()
+            .map(|primitive| primitive.borrow())
+            .filter(|primitive| primitive.get_id() != exclude)
+            .find_map(|primitive| find_nested_anchor(&*primitive, point, tolerance))
+            .map(|(binding, _)| binding)
+    }
+
+    fn bind_endpoints(&self, component: &Rc<RefCell<dyn Component>>, state: &State) {
+        let (id, endpoint_count, handles) = {
+            let component = component.borrow();
+            (component.get_id(), component.get_bindings().len(), component.get_handles())
+        };
+        for (endpoint, handle) in handles.into_iter().enumerate().take(endpoint_count) {
+            let binding = self.find_binding(handle, id, state);
+            component.borrow_mut().set_binding(endpoint, binding);
+        }
+    }
+
+    // Innermost open group first, since connectors inside a group move the anchors it offers to its own siblings
+    fn update_connectors(&mut self) {
+        let open_groups = self.get_open_groups();
+        let mut moved = HashSet::new();
+        for (_, group) in open_groups.iter().rev() {
+            if !follow_bindings(group.borrow().get_children()).is_empty() {
+                moved.extend(open_groups.first().map(|(_, outermost)| outermost.borrow().get_id()));
+            }
+        }
+        moved.extend(follow_bindings(&self.primitives));
+        self.reindex(moved, false);
+    }
+
+    // Brings the spatial index up to date for components that were added, removed or changed. Positions only need
+    // rebuilding when components were added, removed or reordered
+    fn reindex(&mut self, ids: HashSet<ComponentId>, reordered: bool) {
+        if reordered {
+            self.positions = self.primitives.iter()
+                .enumerate()
+                .map(|(index, primitive)| (primitive.borrow().get_id(), index))
+                .collect();
+        }
+        for id in ids {
+            match self.positions.get(&id) {
+                Some(index) => self.index.insert(id, get_hit_bounds(&*self.primitives[*index].borrow())),
+                None => self.index.remove(id),
+            }
+        }
+    }
+
+    // In-place edits go through here rather than straight to the history, so the index follows them
+    fn record(&mut self, command: Command, coalesce: bool) {
+        self.reindex(command.get_ids(), command.is_reordering());
+        self.history.push(command, coalesce);
+    }
+
+    fn allocate_id(&mut self) -> ComponentId {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    // Holding alt only selects components that lie entirely inside the marquee
+    fn update_marquee(&mut self, mouse_pos: Vec2, contained: bool) {
+        let Some(marquee) = self.marquee.as_mut() else {
+            return;
+        };
+        marquee.end = mouse_pos;
+
+        let min = nalgebra_glm::min2(&marquee.start, &marquee.end);
+        let max = nalgebra_glm::max2(&marquee.start, &marquee.end);
+        let mut selection = marquee.initial_selection.clone();
+        let candidates: Vec<_> = match self.open_groups.is_empty() {
+            true => self.query_drawn(min, max)
+                .into_iter()
+                .filter(|primitive| self.is_editable(primitive.borrow().get_layer()))
+                .cloned()
+                .collect(),
+            false => self.get_selectable(),
+        };
+        // The marquee is in world space, which an open group's children are drawn in once transformed
+        let transform = self.get_scope_transform();
+        for primitive in candidates {
+            let vertices = transform.apply_vertices(primitive.borrow().get_vertices(PRECISE_TOLERANCE));
+            let inside = if contained { vertices_within_rect(&vertices, min, max) } else { vertices_intersect_rect(&vertices, min, max) };
+            if inside && !selection.iter().any(|selected| Rc::ptr_eq(selected, &primitive)) {
+                selection.push(primitive);
+            }
+        }
+        self.set_selection(selection);
+    }
+
+    pub fn select_all(&mut self) {
+        self.set_selection(self.get_selectable());
+    }
+
+    pub fn delete_selected(&mut self) {
+        let scope = self.get_scope();
+        let commands: Vec<Command> = self.get_selected_indices()
+            .into_iter()
+            .rev()
+            .map(|index| Command::Delete { index, component: scope[index].clone() })
+            .collect();
+        if !commands.is_empty() {
+            self.execute(self.nest(Command::Batch(commands)), false);
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        !self.selected.is_empty()
+    }
+
+    pub fn get_selection_count(&self) -> usize {
+        self.selected.len()
+    }
+
+    // World space bounds of everything drawn, or of just the selection
+    pub fn get_bounds(&self, selected_only: bool) -> Option<(Vec2, Vec2)> {
+        if selected_only {
+            get_combined_bounds(&self.selected).and_then(|bounds| transform_bounds(&self.get_scope_transform(), bounds))
+        } else {
+            get_combined_bounds(self.get_drawn())
+        }
+    }
+
+    fn translate_all(&mut self, offsets: Vec<(usize, Vec2)>) {
+        let scope = self.get_scope();
+        let commands: Vec<Command> = offsets.into_iter()
+            .filter(|(_, offset)| *offset != vec2(0.0, 0.0))
+            .map(|(index, offset)| {
+                let mut primitive = scope[index].borrow_mut();
+                let before = Box::new(primitive.save());
+                primitive.translate(offset);
+                Command::Modify { index, before, after: Box::new(primitive.save()) }
+            })
+            .collect();
+        if !commands.is_empty() {
+            self.record(self.nest(Command::Batch(commands)), false);
+        }
+    }
+
+    // In the scope's coordinates
+    fn get_selected_bounds(&self) -> Vec<(usize, (Vec2, Vec2))> {
+        let scope = self.get_scope();
+        self.get_selected_indices()
+            .into_iter()
+            .filter_map(|index| Some((index, scope[index].borrow().get_bounds()?)))
+            .collect()
+    }
+
+    // Lines the selection's bounding boxes up with the edge or centre of their combined bounds
+    pub fn align_selected(&mut self, align: Align) {
+        let bounds = self.get_selected_bounds();
+        let Some((min, max)) = bounds.iter().map(|(_, bounds)| *bounds).reduce(merge_bounds) else {
+            return;
+        };
+        let center = (min + max) / 2.0;
+        let offsets = bounds.into_iter().map(|(index, (component_min, component_max))| {
+            let component_center = (component_min + component_max) / 2.0;
+            let offset = match align {
+                Align::Left => vec2(min.x - component_min.x, 0.0),
+                Align::HorizontalCenter => vec2(center.x - component_center.x, 0.0),
+                Align::Right => vec2(max.x - component_max.x, 0.0),
+                Align::Top => vec2(0.0, max.y - component_max.y),
+                Align::VerticalCenter => vec2(0.0, center.y - component_center.y),
+                Align::Bottom => vec2(0.0, min.y - component_min.y),
+            };
+            (index, offset)
+        }).collect();
+        self.translate_all(offsets);
+    }
+
+    // Leaves equal gaps between neighbouring bounding boxes, keeping the outermost two where they are
+    pub fn distribute_selected(&mut self, distribute: Distribute) {
+        let axis = match distribute {
+            Distribute::Horizontal => 0,
+            Distribute::Vertical => 1,
+        };
+        let mut bounds = self.get_selected_bounds();
+        if bounds.len() < 3 {
+            return;
+        }
+        bounds.sort_by(|(_, (min1, max1)), (_, (min2, max2))| (min1[axis] + max1[axis]).total_cmp(&(min2[axis] + max2[axis])));
+
+        // The first and last components stay put, even when a wider one in between reaches past them
+        let start = bounds[0].1.0[axis];
+        let end = bounds[bounds.len() - 1].1.1[axis];
+        let total_size: f32 = bounds.iter().map(|(_, (min, max))| max[axis] - min[axis]).sum();
+        let gap = (end - start - total_size) / (bounds.len() - 1) as f32;
+
+        let mut position = start;
+        let offsets = bounds.into_iter().map(|(index, (min, max))| {
+            let mut offset = vec2(0.0, 0.0);
+            offset[axis] = position - min[axis];
+            position += max[axis] - min[axis] + gap;
+            (index, offset)
+        }).collect();
+        self.translate_all(offsets);
+    }
+
+    // Selected components keep their order relative to each other. Forward and backward step past the next unselected
+    // component on the same layer, so a selection that is already at the top or bottom of its layer stays put
+    fn get_reorder_commands(&self, z_order: ZOrder) -> Vec<Command> {
+        let scope = self.get_scope();
+        let indices = self.get_selected_indices();
+        let last = scope.len().saturating_sub(1);
+        let mut order: Vec<(bool, LayerId)> = scope.iter()
+            .map(|primitive| (self.is_selected(primitive), primitive.borrow().get_layer()))
+            .collect();
+        let mut commands = vec![];
+        match z_order {
+            ZOrder::Front => {
+                for (moved, index) in indices.iter().enumerate() {
+                    commands.push(Command::Reorder { from: index - moved, to: last });
+                }
+            }
+            ZOrder::Back => {
+                for (moved, index) in indices.iter().rev().enumerate() {
+                    commands.push(Command::Reorder { from: index + moved, to: 0 });
+                }
+            }
+            ZOrder::Forward => {
+                for index in indices.into_iter().rev() {
+                    let next = (index + 1..order.len()).find(|other| order[*other].1 == order[index].1);
+                    if let Some(next) = next.filter(|next| !order[*next].0) {
+                        let moved = order.remove(index);
+                        order.insert(next, moved);
+                        commands.push(Command::Reorder { from: index, to: next });
+                    }
+                }
+            }
+            ZOrder::Backward => {
+                for index in indices {
+                    let previous = (0..index).rev().find(|other| order[*other].1 == order[index].1);
+                    if let Some(previous) = previous.filter(|previous| !order[*previous].0) {
+                        let moved = order.remove(index);
+                        order.insert(previous, moved);
+                        commands.push(Command::Reorder { from: index, to: previous });
+                    }
+                }
+            }
+        }
+
+        commands.retain(|command| !matches!(command, Command::Reorder { from, to } if from == to));
+        commands
+    }
+
+    pub fn reorder_selected(&mut self, z_order: ZOrder) {
+        let commands = self.get_reorder_commands(z_order);
+        if !commands.is_empty() {
+            self.execute(self.nest(Command::Batch(commands)), false);
+        }
+    }
+
+    // Moved components go on top of their new layer. Children of an open group stay on the group's layer
+    pub fn move_selected_to_layer(&mut self, layer: LayerId) {
+        if !self.open_groups.is_empty() {
+            return;
+        }
+
+        let mut commands: Vec<Command> = self.get_selected_indices()
+            .into_iter()
+            .filter(|index| self.primitives[*index].borrow().get_layer() != layer)
+            .map(|index| {
+                let mut primitive = self.primitives[index].borrow_mut();
+                let before = Box::new(primitive.save());
+                primitive.set_layer(layer);
+                Command::Modify { index, before, after: Box::new(primitive.save()) }
+            })
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+
+        let reorders = self.get_reorder_commands(ZOrder::Front);
+        for command in &reorders {
+            command.apply(&mut self.primitives, &mut self.layers);
+        }
+        commands.extend(reorders);
+        self.record(Command::Batch(commands), false);
+        self.deselect_removed();
+    }
+
+    pub fn get_layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn get_active_layer(&self) -> LayerId {
+        self.active_layer
+    }
+
+    pub fn set_active_layer(&mut self, layer: LayerId) {
+        self.active_layer = layer;
+    }
+
+    // Edits that keep being made over several frames, like typing a name, coalesce after `begin_edit`
+    pub fn set_layers(&mut self, layers: Vec<Layer>, coalesce: bool) {
+        if layers != self.layers {
+            self.execute(Command::Layers { before: self.layers.clone(), after: layers }, coalesce);
+        }
+    }
+
+    // New layers go directly above the active one and become active
+    pub fn add_layer(&mut self) {
+        let id = self.layers.iter().map(|layer| layer.id + 1).max().unwrap_or(0);
+        let index = self.layers.iter().position(|layer| layer.id == self.active_layer).map_or(self.layers.len(), |index| index + 1);
+        // The first name not already taken, so deleting a layer doesn't lead to two with the same name
+        let name = (1..).map(|n| format!("Layer {}", n)).find(|name| self.layers.iter().all(|layer| layer.name != *name)).unwrap_or_default();
+        let mut layers = self.layers.clone();
+        layers.insert(index, Layer::new(id, name));
+        self.set_layers(layers, false);
+        self.active_layer = id;
+    }
+
+    // Takes the layer's components with it. The last layer can't be deleted
+    pub fn delete_layer(&mut self, layer: LayerId) {
+        if self.layers.len() <= 1 {
+            return;
+        }
+
+        let mut commands: Vec<Command> = self.primitives.iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, primitive)| primitive.borrow().get_layer() == layer)
+            .map(|(index, primitive)| Command::Delete { index, component: primitive.clone() })
+            .collect();
+        let mut layers = self.layers.clone();
+        layers.retain(|other| other.id != layer);
+        commands.push(Command::Layers { before: self.layers.clone(), after: layers });
+        self.execute(Command::Batch(commands), false);
+    }
+
+    fn save_selected(&self) -> Vec<SavedComponent> {
+        let scope = self.get_scope();
+        self.get_selected_indices().into_iter().map(|index| scope[index].borrow().save()).collect()
+    }
+
+    // Adds the components on top of everything else in the scope and selects them
+    fn add_components(&mut self, components: Vec<Rc<RefCell<dyn Component>>>) {
+        if components.is_empty() {
+            return;
+        }
+
+        // Copies get fresh IDs, down into groups; bindings between copied components follow them, bindings to anything else are dropped
+        let mut ids: HashMap<ComponentId, ComponentId> = HashMap::new();
+        for component in &components {
+            visit_nested(&mut *component.borrow_mut(), &mut |nested| {
+                ids.insert(nested.get_id(), self.allocate_id());
+            });
+        }
+        let layer = self.get_open_groups().first().map_or(self.active_layer, |(_, group)| group.borrow().get_layer());
+        for component in &components {
+            let mut component = component.borrow_mut();
+            component.set_layer(layer);
+            visit_nested(&mut *component, &mut |nested| {
+                nested.set_id(ids[&nested.get_id()]);
+                for (endpoint, binding) in nested.get_bindings().into_iter().enumerate() {
+                    let binding = binding.and_then(|binding| Some(Binding { component: *ids.get(&binding.component)?, ..binding }));
+                    nested.set_binding(endpoint, binding);
+                }
+            });
+        }
+
+        let start = self.get_scope().len();
+        let commands = components.iter()
+            .enumerate()
+            .map(|(i, component)| Command::Add { index: start + i, component: component.clone() })
+            .collect();
+        self.execute(self.nest(Command::Batch(commands)), false);
+        self.set_selection(components);
+    }
+
+    // Puts `components` where the components at `indices` were, on top of the others' position, and selects them
+    fn replace(&mut self, indices: Vec<usize>, components: Vec<Rc<RefCell<dyn Component>>>) {
+        let Some(top) = indices.last() else {
+            return;
+        };
+
+        let start = top + 1 - indices.len();
+        let scope = self.get_scope();
+        let mut commands: Vec<Command> = indices.iter()
+            .rev()
+            .map(|index| Command::Delete { index: *index, component: scope[*index].clone() })
+            .collect();
+        commands.extend(components.iter()
+            .enumerate()
+            .map(|(i, component)| Command::Add { index: start + i, component: component.clone() }));
+        self.execute(self.nest(Command::Batch(commands)), false);
+        self.set_selection(components);
+    }
+
+    // The group's children as standalone components, with the group's transform folded into theirs
+    fn get_ungrouped(&self, index: usize) -> Vec<Rc<RefCell<dyn Component>>> {
+        let group = self.get_scope()[index].clone();
+        let group = group.borrow();
+        let transform = group.get_transform();
+        group.get_children()
+            .iter()
+            .map(|child| {
+                let child = child.borrow().save().load();
+                let child_transform = child.borrow().get_transform();
+                child.borrow_mut().set_transform(child_transform.then(&transform));
+                child.borrow_mut().set_layer(group.get_layer());
+                child
+            })
+            .collect()
+    }
+
+    // Goes on the layer of the topmost selected component
+    pub fn group_selected(&mut self) {
+        let indices = self.get_selected_indices();
+        let [.., top] = indices[..] else {
+            return;
+        };
+        if indices.len() < 2 {
+            return;
+        }
+
+        let scope = self.get_scope();
+        let layer = scope[top].borrow().get_layer();
+        let children = indices.iter().map(|index| {
+            let child = scope[*index].borrow().save().load();
+            child.borrow_mut().set_layer(layer);
+            child
+        }).collect();
+        let mut group = Group::new(children, self.style, Label::new(""), Transform::default());
+        group.set_id(self.allocate_id());
+        group.set_layer(layer);
+        self.replace(indices, vec![Rc::new(RefCell::new(group))]);
+    }
+
+    pub fn ungroup_selected(&mut self) {
+        let scope = self.get_scope();
+        let indices: Vec<usize> = self.get_selected_indices()
+            .into_iter()
+            .filter(|index| !scope[*index].borrow().get_children().is_empty())
+            .collect();
+        if indices.is_empty() {
+            return;
+        }
+
+        let mut commands = vec![];
+        let mut selection: Vec<_> = self.selected.iter()
+            .filter(|selected| selected.borrow().get_children().is_empty())
+            .cloned()
+            .collect();
+        for index in indices.into_iter().rev() {
+            let children = self.get_ungrouped(index);
+            commands.push(Command::Delete { index, component: scope[index].clone() });
+            commands.extend(children.iter()
+                .enumerate()
+                .map(|(i, child)| Command::Add { index: index + i, component: child.clone() }));
+            selection.extend(children);
+        }
+        self.execute(self.nest(Command::Batch(commands)), false);
+        self.set_selection(selection);
+    }
+
+    // Opening a group only changes what can be selected, so it is not an undo step. Selects the child under the mouse
+    fn open_group(&mut self, mouse_pos: Vec2) {
+        let Some(hovered) = self.get_hovered(mouse_pos) else {
+            return;
+        };
+        if hovered.borrow().get_children().is_empty() {
+            return;
+        }
+
+        self.open_groups.push(hovered.borrow().get_id());
+        self.drag = None;
+        let selection = self.get_hovered(mouse_pos);
+        self.set_selection(selection.into_iter().collect());
+    }
+
+    // Selects the group that was closed
+    fn close_group(&mut self) {
+        let Some((_, group)) = self.get_open_groups().pop() else {
+            return;
+        };
+        self.open_groups.pop();
+        self.set_selection(vec![group]);
+    }
+
+    fn close_groups(&mut self) {
+        self.open_groups.clear();
+        self.deselect_removed();
+    }
+
+    // Clicking anything but a child of the innermost open group closes it, and so on outwards
+    fn close_groups_outside(&mut self, mouse_pos: Vec2) {
+        while !self.open_groups.is_empty() && self.get_hovered(mouse_pos).is_none() {
+            self.open_groups.pop();
+            self.deselect_removed();
+        }
+    }
+
+    pub fn copy_selected(&mut self, ui: &Ui) {
+        let components = self.save_selected();
+        if components.is_empty() {
+            return;
+        }
+
+        ui.set_clipboard_text(clipboard::to_text(&components));
+        self.clipboard = components;
+    }
+
+    // Prefers the system clipboard so components copied in another window can be pasted here
+    pub fn paste(&mut self, ui: &Ui, mouse_pos: Vec2) {
+        let mouse_pos = self.get_scope_transform().invert(mouse_pos);
+        let components: Vec<_> = ui.clipboard_text()
+            .and_then(|text| clipboard::from_text(&text))
+            .unwrap_or_else(|| self.clipboard.clone())
+            .into_iter()
+            .map(SavedComponent::load)
+            .collect();
+
+        let offset = match get_combined_bounds(&components) {
+            Some((min, max)) => mouse_pos - (min + max) / 2.0,
+            None => vec2(0.0, 0.0),
+        };
+        for component in &components {
+            component.borrow_mut().translate(offset);
+        }
+        self.add_components(components);
+    }
+
+    pub fn duplicate_selected(&mut self) {
+        let components: Vec<_> = self.save_selected().into_iter().map(SavedComponent::load).collect();
+        for component in &components {
+            component.borrow_mut().translate(DUPLICATE_OFFSET);
+        }
+        self.add_components(components);
+    }
+
+    // The selection's styles for the properties panel to compare. Never empty: with nothing selected, it is the style
+    // new components are placed in
+    pub fn get_styles(&self) -> Vec<Style> {
+        let styles: Vec<Style> = self.selected.iter().flat_map(|selected| selected.borrow().get_styles()).collect();
+        match styles.is_empty() {
+            true => vec![self.style],
+            false => styles,
+        }
+    }
+
+    pub fn get_label(&self) -> Option<Label> {
+        match self.selected.as_slice() {
+            [selected] => Some(selected.borrow().get_label()),
+            _ => None,
+        }
+    }
+
+    pub fn set_label(&mut self, label: Label) {
+        let [index] = self.get_selected_indices()[..] else {
+            return;
+        };
+
+        let scope = self.get_scope();
+        let command = {
+            let mut primitive = scope[index].borrow_mut();
+            let before = Box::new(primitive.save());
+            primitive.set_label(label);
+            Command::Modify { index, before, after: Box::new(primitive.save()) }
+        };
+        self.record(self.nest(command), true);
+    }
+
+    // The single selected component's transform, relative to any open group, and where its pivot is in world space
+    pub fn get_transform(&self) -> Option<(Vec2, Transform)> {
+        match self.selected.as_slice() {
+            [selected] => {
+                let selected = selected.borrow();
+                Some((self.get_scope_transform().apply(get_pivot(&*selected)), selected.get_transform()))
+            }
+            _ => None,
+        }
+    }
+
+    // Applies the rotation, scale and skew of `transform` about the pivot, then moves the pivot to `pivot`
+    pub fn set_transform(&mut self, pivot: Vec2, mut transform: Transform, state: &State) {
+        let [index] = self.get_selected_indices()[..] else {
+            return;
+        };
+
+        let scope = self.get_scope();
+        let pivot = self.get_scope_transform().invert(pivot);
+        let before = {
+            let mut primitive = scope[index].borrow_mut();
+            let before = Box::new(primitive.save());
+            let local_pivot = primitive.get_transform().invert(get_pivot(&*primitive));
+            transform.translate(pivot - transform.apply(local_pivot));
+            primitive.set_transform(transform);
+            before
+        };
+        self.bind_endpoints(&scope[index], state);
+        let after = Box::new(scope[index].borrow().save());
+        self.record(self.nest(Command::Modify { index, before, after }), true);
+    }
+
+    pub fn begin_edit(&mut self) {
+        self.history.seal();
+    }
+
+    // Applies `edit` to each selected component's own style, and to the style new components are placed in
+    pub fn edit_style(&mut self, mut edit: impl FnMut(&mut Style), coalesce: bool) {
+        edit(&mut self.style);
+        let indices = self.get_selected_indices();
+        if indices.is_empty() {
+            return;
+        }
+
+        let scope = self.get_scope();
+        let commands = indices.into_iter().map(|index| {
+            let mut primitive = scope[index].borrow_mut();
+            let before = Box::new(primitive.save());
+            primitive.edit_style(&mut edit);
+            Command::Modify { index, before, after: Box::new(primitive.save()) }
+        }).collect();
+        self.record(self.nest(Command::Batch(commands)), coalesce);
+    }
+
+    // New components are placed on the top level, so picking a tool closes any open groups
     pub fn set_placer(&mut self, component_type: ComponentType) {
         self.placer = if component_type == ComponentType::None {
             None
         } else {
+            self.close_groups();
             Some(Placer::new(component_type))
         };
     }
@@ -75,11 +1243,397 @@ impl ComponentLayer {
         self.placer.is_some()
     }
 
-    pub fn click_placer(&mut self, world_coords: Vec2, state: &State) {
+    pub fn is_placing(&self) -> bool {
+        self.placer.as_ref().is_some_and(Placer::is_in_progress)
+    }
+
+    // The second click of a double-click finishes open-ended tools rather than adding a vertex
+    pub fn click_placer(&mut self, world_coords: Vec2, state: &State, double_click: bool) {
+        let (world_coords, _) = self.snap(world_coords, state);
         let placer = self.placer.as_mut().expect("Attempted to click nonexistant placer");
+        if double_click && placer.is_open_ended() {
+            self.complete_placer(state);
+            return;
+        }
+
         placer.clicked(world_coords);
-        if let Some(component) = placer.finish(state) {
-            self.primitives.push(component);
+        if let Some(component) = placer.finish(self.style) {
+            self.add_placed(component, state);
+        }
+    }
+
+    pub fn complete_placer(&mut self, state: &State) {
+        if let Some(component) = self.placer.as_mut().and_then(|placer| placer.complete(self.style)) {
+            self.add_placed(component, state);
         }
     }
-}
\ No newline at end of file
+
+    fn add_placed(&mut self, component: Rc<RefCell<dyn Component>>, state: &State) {
+        let id = self.allocate_id();
+        component.borrow_mut().set_id(id);
+        component.borrow_mut().set_layer(self.active_layer);
+        self.bind_endpoints(&component, state);
+        self.execute(Command::Add { index: self.primitives.len(), component }, false);
+    }
+
+    pub fn execute(&mut self, command: Command, coalesce: bool) {
+        command.apply(&mut self.primitives, &mut self.layers);
+        self.record(command, coalesce);
+        self.update_layers();
+    }
+
+    pub fn undo(&mut self) {
+        let changes = self.history.undo(&mut self.primitives, &mut self.layers).map(|command| (command.get_ids(), command.is_reordering()));
+        let (ids, reordered) = changes.unwrap_or_default();
+        self.reindex(ids, reordered);
+        self.update_layers();
+    }
+
+    pub fn redo(&mut self) {
+        let changes = self.history.redo(&mut self.primitives, &mut self.layers).map(|command| (command.get_ids(), command.is_reordering()));
+        let (ids, reordered) = changes.unwrap_or_default();
+        self.reindex(ids, reordered);
+        self.update_layers();
+    }
+
+    // Keeps the active layer and the selection valid after layers or components come and go
+    fn update_layers(&mut self) {
+        if !self.layers.iter().any(|layer| layer.id == self.active_layer) {
+            self.active_layer = self.layers.last().map_or(0, |layer| layer.id);
+        }
+        self.deselect_removed();
+    }
+
+    pub fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        self.get_drawn().into_iter().flat_map(|primitive| primitive.borrow().get_vertices(tolerance)).collect()
+    }
+
+    pub fn to_svg(&self) -> Vec<String> {
+        self.get_drawn().into_iter().map(|primitive| primitive.borrow().to_svg()).collect()
+    }
+
+    pub fn save(&self) -> Vec<SavedComponent> {
+        self.primitives.iter().map(|primitive| primitive.borrow().save()).collect()
+    }
+
+    pub fn load(&mut self, layers: Vec<Layer>, components: Vec<SavedComponent>) {
+        self.set_placer(ComponentType::None);
+        self.selected.clear();
+        self.active_layer = layers.last().map_or(0, |layer| layer.id);
+        self.layers = layers;
+        self.open_groups.clear();
+        self.primitives = components.into_iter().map(SavedComponent::load).collect();
+        self.next_id = self.primitives.iter().map(|primitive| get_max_id(&*primitive.borrow()) + 1).max().unwrap_or(1);
+        self.history.clear();
+        self.index.clear();
+        self.reindex(self.primitives.iter().map(|primitive| primitive.borrow().get_id()).collect(), true);
+        self.update_connectors();
+    }
+}
+
+// Mean of the snap points, which stays in the same place relative to the component however it is transformed
+fn get_pivot(component: &dyn Component) -> Vec2 {
+    let points = component.get_snap_points();
+    points.iter().sum::<Vec2>() / points.len().max(1) as f32
+}
+
+// Axis-aligned world bounds of a box in the coordinates `transform` maps from
+fn transform_bounds(transform: &Transform, (min, max): (Vec2, Vec2)) -> Option<(Vec2, Vec2)> {
+    transform.get_bounds(&[min, vec2(min.x, max.y), max, vec2(max.x, min.y)].map(|corner| (corner, 0.0)))
+}
+
+fn get_combined_bounds<'a>(components: impl IntoIterator<Item = &'a Rc<RefCell<dyn Component>>>) -> Option<(Vec2, Vec2)> {
+    components.into_iter().filter_map(|component| component.borrow().get_bounds()).reduce(merge_bounds)
+}
+
+// Half the widest stroke, in world space
+fn get_stroke_reach(component: &dyn Component) -> f32 {
+    let reach = component.get_children()
+        .iter()
+        .map(|child| get_stroke_reach(&*child.borrow()))
+        .fold(component.get_style().stroke_width / 2.0, f32::max);
+    reach * component.get_transform().get_stretch()
+}
+
+// Hit-testing measures strokes out to half their width all round, past the butt caps and bevels that are drawn,
+// so the spatial index pads the drawn bounds to match
+fn get_hit_bounds(component: &dyn Component) -> Option<(Vec2, Vec2)> {
+    let (min, max) = component.get_bounds()?;
+    let reach = get_stroke_reach(component);
+    Some((min - vec2(reach, reach), max + vec2(reach, reach)))
+}
+
+// Group children aren't in the index, and open groups are small enough to search
+fn find_child(children: &[Rc<RefCell<dyn Component>>], id: ComponentId) -> Option<(usize, Rc<RefCell<dyn Component>>)> {
+    let index = children.iter().position(|child| child.borrow().get_id() == id)?;
+    Some((index, children[index].clone()))
+}
+
+// Moves bound endpoints onto their anchors on other `components`, returning the connectors that moved. Endpoints
+// bound to a component that no longer exists stay where they are, so undoing the delete reattaches them
+fn follow_bindings(components: &[Rc<RefCell<dyn Component>>]) -> HashSet<ComponentId> {
+    // Which of `components` each ID belongs to, since bindings can be to something inside a group
+    let owners: HashMap<ComponentId, usize> = components.iter()
+        .enumerate()
+        .flat_map(|(index, component)| get_nested_ids(&*component.borrow()).into_iter().map(move |id| (id, index)))
+        .collect();
+    let mut moved = HashSet::new();
+    for component in components {
+        let bindings = component.borrow().get_bindings();
+        for (endpoint, binding) in bindings.into_iter().enumerate() {
+            let Some(position) = binding.and_then(|binding| get_nested_anchor(&*components[*owners.get(&binding.component)?].borrow(), &binding)) else {
+                continue;
+            };
+            if component.borrow().get_handles()[endpoint] != position {
+                component.borrow_mut().move_handle(endpoint, position);
+                moved.insert(component.borrow().get_id());
+            }
+        }
+    }
+    moved
+}
+
+// The nearest point on the outline of the component or, for a group, of anything inside it
+fn find_nested_anchor(component: &dyn Component, point: Vec2, tolerance: f32) -> Option<(Binding, Vec2)> {
+    if component.get_children().is_empty() {
+        let position = component.find_anchor(point, tolerance)?;
+        return Some((Binding { component: component.get_id(), position }, component.get_anchor(position)?));
+    }
+
+    let transform = component.get_transform();
+    let (local_point, local_tolerance) = (transform.invert(point), transform.get_local_tolerance(tolerance));
+    component.get_children().iter()
+        .filter_map(|child| find_nested_anchor(&*child.borrow(), local_point, local_tolerance))
+        .map(|(binding, anchor)| (binding, transform.apply(anchor)))
+        .min_by(|(_, anchor1), (_, anchor2)| (anchor1 - point).magnitude().total_cmp(&(anchor2 - point).magnitude()))
+}
+
+// Where the binding is, if it is to the component or to anything inside it
+fn get_nested_anchor(component: &dyn Component, binding: &Binding) -> Option<Vec2> {
+    if component.get_id() == binding.component {
+        return component.get_anchor(binding.position);
+    }
+
+    let transform = component.get_transform();
+    component.get_children().iter()
+        .find_map(|child| get_nested_anchor(&*child.borrow(), binding))
+        .map(|anchor| transform.apply(anchor))
+}
+
+fn get_nested_ids(component: &dyn Component) -> Vec<ComponentId> {
+    let mut ids = vec![component.get_id()];
+    for child in component.get_children() {
+        ids.extend(get_nested_ids(&*child.borrow()));
+    }
+    ids
+}
+
+// The component itself, then everything inside it
+fn visit_nested(component: &mut dyn Component, visit: &mut dyn FnMut(&mut dyn Component)) {
+    visit(component);
+    for child in component.get_children() {
+        visit_nested(&mut *child.borrow_mut(), visit);
+    }
+}
+
+fn get_max_id(component: &dyn Component) -> ComponentId {
+    component.get_children().iter().map(|child| get_max_id(&*child.borrow())).fold(component.get_id(), ComponentId::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::{connector::Connector, rect::Rect};
+
+    use super::*;
+
+    fn add_rect(layer: &mut ComponentLayer, from: Vec2, to: Vec2) -> Rc<RefCell<dyn Component>> {
+        let rect: Rc<RefCell<dyn Component>> = Rc::new(RefCell::new(Rect::new(vec![from, to], Style::default())));
+        layer.add_placed(rect.clone(), &State::new());
+        rect
+    }
+
+    fn add_connector(layer: &mut ComponentLayer, from: Vec2, to: Vec2, state: &State) -> Rc<RefCell<dyn Component>> {
+        let connector: Rc<RefCell<dyn Component>> = Rc::new(RefCell::new(Connector::new(vec![from, to], Style::default())));
+        layer.add_placed(connector.clone(), state);
+        layer.update_connectors();
+        connector
+    }
+
+    #[test]
+    fn connectors_bind_to_nearby_outlines_without_object_snapping() {
+        let mut layer = ComponentLayer::new();
+        let mut state = State::new();
+        state.toggle_object_snapping();
+        let rect = add_rect(&mut layer, vec2(0.0, 0.0), vec2(100.0, 100.0));
+        let connector = add_connector(&mut layer, vec2(300.0, 50.0), vec2(103.0, 50.0), &state);
+
+        let id = rect.borrow().get_id();
+        assert!(matches!(connector.borrow().get_bindings()[..], [None, Some(Binding { component, position })] if component == id && position == 0.625));
+        assert_eq!(connector.borrow().get_handles()[1], vec2(100.0, 50.0));
+
+        // Resizing keeps the endpoint the same way along the same edge
+        rect.borrow_mut().move_handle(2, vec2(120.0, 200.0));
+        layer.update_connectors();
+        assert_eq!(connector.borrow().get_handles()[1], vec2(120.0, 100.0));
+    }
+
+    #[test]
+    fn connectors_bind_to_shapes_inside_groups() {
+        let mut layer = ComponentLayer::new();
+        group_two_rects(&mut layer);
+        let connector = add_connector(&mut layer, vec2(25.0, 50.0), vec2(25.0, 12.0), &State::new());
+
+        let group = layer.primitives[0].clone();
+        let second = group.borrow().get_children()[1].borrow().get_id();
+        assert!(matches!(connector.borrow().get_bindings()[..], [None, Some(Binding { component, .. })] if component == second));
+
+        group.borrow_mut().translate(vec2(0.0, 100.0));
+        layer.update_connectors();
+        assert_eq!(connector.borrow().get_handles()[1], vec2(25.0, 110.0));
+    }
+
+    #[test]
+    fn undo_and_redo_keep_the_selection() {
+        let mut layer = ComponentLayer::new();
+        let rect = add_rect(&mut layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        layer.set_selection(vec![rect.clone()]);
+        layer.edit_style(|style| style.stroke_width = 6.0, false);
+
+        for step in [ComponentLayer::undo, ComponentLayer::redo] {
+            step(&mut layer);
+            assert!(matches!(layer.selected.as_slice(), [selected] if Rc::ptr_eq(selected, &rect)));
+            assert!(Rc::ptr_eq(&layer.primitives[0], &rect));
+        }
+        assert_eq!(rect.borrow().get_style().stroke_width, 6.0);
+    }
+
+    #[test]
+    fn style_edits_change_only_the_edited_field() {
+        let mut layer = ComponentLayer::new();
+        let red = add_rect(&mut layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let blue = add_rect(&mut layer, vec2(20.0, 0.0), vec2(30.0, 10.0));
+        blue.borrow_mut().set_style(Style { fill: [0.0, 0.0, 1.0, 1.0], ..Style::default() });
+        layer.set_selection(vec![red.clone(), blue.clone()]);
+
+        assert!(layer.get_styles() == [Style::default(), Style { fill: [0.0, 0.0, 1.0, 1.0], ..Style::default() }]);
+        layer.edit_style(|style| style.stroke_width = 6.0, false);
+        assert!(red.borrow().get_style() == Style { stroke_width: 6.0, ..Style::default() });
+        assert!(blue.borrow().get_style() == Style { fill: [0.0, 0.0, 1.0, 1.0], stroke_width: 6.0, ..Style::default() });
+    }
+
+    #[test]
+    fn distributing_keeps_the_first_and_last_in_place() {
+        let mut layer = ComponentLayer::new();
+        let first = add_rect(&mut layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let wide = add_rect(&mut layer, vec2(-50.0, 20.0), vec2(100.0, 30.0));
+        let last = add_rect(&mut layer, vec2(90.0, 40.0), vec2(100.0, 50.0));
+        layer.set_selection(vec![first.clone(), wide, last.clone()]);
+        let before = [first.borrow().get_bounds(), last.borrow().get_bounds()];
+
+        layer.distribute_selected(Distribute::Horizontal);
+        assert_eq!([first.borrow().get_bounds(), last.borrow().get_bounds()], before);
+    }
+
+    #[test]
+    fn picking_follows_deletes_and_undo() {
+        let mut layer = ComponentLayer::new();
+        let bottom = add_rect(&mut layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let top = add_rect(&mut layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let right = add_rect(&mut layer, vec2(500.0, 0.0), vec2(510.0, 10.0));
+        let picked = |layer: &ComponentLayer, at: Vec2, expected: &Rc<RefCell<dyn Component>>| {
+            matches!(layer.get_hovered(at), Some(hovered) if Rc::ptr_eq(&hovered, expected))
+        };
+        assert!(picked(&layer, vec2(5.0, 5.0), &top));
+
+        layer.set_selection(vec![top.clone()]);
+        layer.delete_selected();
+        assert!(picked(&layer, vec2(5.0, 5.0), &bottom));
+        assert!(picked(&layer, vec2(505.0, 5.0), &right));
+
+        layer.undo();
+        assert!(picked(&layer, vec2(5.0, 5.0), &top));
+        assert!(picked(&layer, vec2(505.0, 5.0), &right));
+    }
+
+    #[test]
+    fn points_snap_to_nearby_components() {
+        let mut layer = ComponentLayer::new();
+        let state = State::new();
+        add_rect(&mut layer, vec2(0.0, 0.0), vec2(100.0, 100.0));
+        add_rect(&mut layer, vec2(1000.0, 0.0), vec2(1100.0, 100.0));
+
+        assert_eq!(layer.find_snap(&[vec2(300.0, 50.0), vec2(1103.0, 98.0)], &state), Some((vec2(-3.0, 2.0), vec2(1100.0, 100.0))));
+        assert_eq!(layer.find_snap(&[vec2(500.0, 50.0)], &state), None);
+    }
+
+    #[test]
+    fn new_layers_take_the_first_unused_name() {
+        let mut layer = ComponentLayer::new();
+        layer.add_layer();
+        layer.add_layer();
+        layer.delete_layer(layer.layers[1].id);
+        layer.add_layer();
+
+        let names: Vec<&str> = layer.layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, ["Layer 1", "Layer 3", "Layer 2"]);
+    }
+
+    fn group_two_rects(layer: &mut ComponentLayer) {
+        let first = add_rect(layer, vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let second = add_rect(layer, vec2(20.0, 0.0), vec2(30.0, 10.0));
+        layer.set_selection(vec![first, second]);
+        layer.group_selected();
+    }
+
+    #[test]
+    fn restyling_a_group_keeps_its_childrens_other_fields() {
+        let mut layer = ComponentLayer::new();
+        group_two_rects(&mut layer);
+        let group = layer.primitives[0].clone();
+        let children = group.borrow().get_children().to_vec();
+        children[1].borrow_mut().set_style(Style { fill: [0.0, 0.0, 1.0, 1.0], ..Style::default() });
+        layer.set_selection(vec![group]);
+
+        layer.edit_style(|style| style.stroke_width = 6.0, false);
+        assert!(children[0].borrow().get_style() == Style { stroke_width: 6.0, ..Style::default() });
+        assert!(children[1].borrow().get_style() == Style { fill: [0.0, 0.0, 1.0, 1.0], stroke_width: 6.0, ..Style::default() });
+    }
+
+    #[test]
+    fn open_groups_edit_their_children_in_place() {
+        let mut layer = ComponentLayer::new();
+        group_two_rects(&mut layer);
+        layer.open_group(vec2(5.0, 5.0));
+
+        let child = layer.primitives[0].borrow().get_children()[0].clone();
+        assert!(matches!(layer.selected.as_slice(), [selected] if Rc::ptr_eq(selected, &child)));
+        layer.edit_style(|style| style.stroke_width = 6.0, false);
+        assert!(matches!(layer.save().as_slice(), [SavedComponent::Group(_)]));
+
+        // Opening the group was not a step of its own, so the next undo takes the group away
+        for (stroke_width, grouped) in [(2.0, true), (2.0, false)] {
+            layer.undo();
+            assert_eq!(child.borrow().get_style().stroke_width, stroke_width);
+            assert_eq!(layer.primitives.len(), if grouped { 1 } else { 2 });
+            assert_eq!(layer.open_groups.len(), if grouped { 1 } else { 0 });
+            assert_eq!(layer.is_selected(&child), grouped);
+        }
+    }
+
+    #[test]
+    fn children_are_picked_through_the_group_transform() {
+        let mut layer = ComponentLayer::new();
+        group_two_rects(&mut layer);
+        layer.translate_all(vec![(0, vec2(100.0, 0.0))]);
+        layer.open_group(vec2(125.0, 5.0));
+
+        let child = layer.primitives[0].borrow().get_children()[1].clone();
+        assert!(matches!(layer.selected.as_slice(), [selected] if Rc::ptr_eq(selected, &child)));
+        assert_eq!(layer.get_bounds(true), Some((vec2(119.0, -1.0), vec2(131.0, 11.0))));
+
+        layer.close_group();
+
+        assert!(layer.open_groups.is_empty());
+        assert!(matches!(layer.selected.as_slice(), [selected] if Rc::ptr_eq(selected, &layer.primitives[0])));
+    }
+}
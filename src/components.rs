@@ -1,16 +1,48 @@
 use std::{rc::Rc, cell::RefCell};
 
-use nalgebra_glm::Vec2;
+use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
 
-use crate::state::State;
-
-use self::{triangle::Triangle, line::Line, circle::Circle, rect::Rect};
+use self::{triangle::Triangle, line::Line, circle::Circle, connector::Connector, curve::Curve, ellipse::Ellipse, group::Group, polygon::Polygon, polyline::Polyline, rect::Rect, text::{Text, Label}, style::{Style, Color}, transform::Transform, properties::Properties};
 
 pub mod circle;
+pub mod connector;
+pub mod curve;
+pub mod ellipse;
+pub mod font;
+pub mod group;
 pub mod line;
+pub mod marker;
+pub mod polygon;
+pub mod polyline;
+pub mod properties;
 pub mod rect;
+pub mod stroke;
+pub mod style;
+pub mod text;
+pub mod transform;
 pub mod triangle;
 
+// x, y, r, g, b, a
+pub const VERTEX_SIZE: usize = 6;
+// Flattening tolerance in world units for geometry that must be accurate whatever the zoom, like hit-testing and bounds
+pub const PRECISE_TOLERANCE: f32 = 0.05;
+
+// Kept finite for huge shapes seen from close up, where most of the outline is off screen anyway
+const MAX_ARC_SEGMENTS: usize = 4096;
+
+// Unique within a document and stable across saves, unlike Rc identity. 0 means not yet added to a layer
+pub type ComponentId = u64;
+pub type LayerId = u64;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    // The component inside a group rather than the group, so bindings survive grouping and ungrouping
+    pub component: ComponentId,
+    // How far along the component's outline, as passed to `Component::get_anchor`
+    pub position: f32,
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum ComponentType {
     None,
@@ -18,48 +50,313 @@ pub enum ComponentType {
     Line,
     Rect,
     Circle,
+    Connector,
+    Text,
+    Polyline,
+    Polygon,
+    Curve,
+    Ellipse,
 }
 
 impl ComponentType {
-    pub fn build(&self, vertices: Vec<Vec2>, state: &State) -> Rc<RefCell<dyn Component>> {
+    pub fn build(&self, vertices: Vec<Vec2>, style: Style) -> Rc<RefCell<dyn Component>> {
         match self {
             ComponentType::None => panic!(),
-            ComponentType::Triangle => Rc::new(RefCell::new(Triangle::new(vertices, state))),
-            ComponentType::Line => Rc::new(RefCell::new(Line::new(vertices, state))),
-            ComponentType::Rect => Rc::new(RefCell::new(Rect::new(vertices, state))),
-            ComponentType::Circle => Rc::new(RefCell::new(Circle::new(vertices, state))),
+            ComponentType::Triangle => Rc::new(RefCell::new(Triangle::new(vertices, style))),
+            ComponentType::Line => Rc::new(RefCell::new(Line::new(vertices, style))),
+            ComponentType::Rect => Rc::new(RefCell::new(Rect::new(vertices, style))),
+            ComponentType::Circle => Rc::new(RefCell::new(Circle::new(vertices, style))),
+            ComponentType::Connector => Rc::new(RefCell::new(Connector::new(vertices, style))),
+            ComponentType::Text => Rc::new(RefCell::new(Text::new(vertices, style))),
+            ComponentType::Polyline => Rc::new(RefCell::new(Polyline::new(vertices, style))),
+            ComponentType::Polygon => Rc::new(RefCell::new(Polygon::new(vertices, style))),
+            ComponentType::Curve => Rc::new(RefCell::new(Curve::new(vertices, style))),
+            ComponentType::Ellipse => Rc::new(RefCell::new(Ellipse::new(vertices, style))),
         }
     }
 
-    pub fn preview(&self, vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>> {
+    pub fn preview(&self, vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
         match self {
             ComponentType::None => panic!(),
-            ComponentType::Triangle => Triangle::get_preview(vertices, state),
-            ComponentType::Line => Line::get_preview(vertices, state),
-            ComponentType::Rect => Rect::get_preview(vertices, state),
-            ComponentType::Circle => Circle::get_preview(vertices, state),
+            ComponentType::Triangle => Triangle::get_preview(vertices, style),
+            ComponentType::Line => Line::get_preview(vertices, style),
+            ComponentType::Rect => Rect::get_preview(vertices, style),
+            ComponentType::Circle => Circle::get_preview(vertices, style),
+            ComponentType::Connector => Connector::get_preview(vertices, style),
+            ComponentType::Text => Text::get_preview(vertices, style),
+            ComponentType::Polyline => Polyline::get_preview(vertices, style),
+            ComponentType::Polygon => Polygon::get_preview(vertices, style),
+            ComponentType::Curve => Curve::get_preview(vertices, style),
+            ComponentType::Ellipse => Ellipse::get_preview(vertices, style),
         }
     }
 
-    pub fn get_vertex_count(&self) -> u32 {
+    // None for tools that keep taking clicks until they are finished explicitly
+    pub fn get_vertex_count(&self) -> Option<u32> {
         match self {
             ComponentType::None => panic!(),
-            ComponentType::Triangle => 3,
-            ComponentType::Line => 2,
-            ComponentType::Rect => 2,
-            ComponentType::Circle => 2,
+            ComponentType::Triangle => Some(3),
+            ComponentType::Line => Some(2),
+            ComponentType::Rect => Some(2),
+            ComponentType::Circle => Some(2),
+            ComponentType::Ellipse => Some(2),
+            ComponentType::Connector => Some(2),
+            ComponentType::Text => Some(1),
+            ComponentType::Polyline | ComponentType::Polygon | ComponentType::Curve => None,
+        }
+    }
+
+    pub fn get_min_vertex_count(&self) -> u32 {
+        match self {
+            ComponentType::Polygon => 3,
+            _ => self.get_vertex_count().unwrap_or(2),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SavedComponent {
+    Triangle(Triangle),
+    Line(Line),
+    Rect(Rect),
+    Circle(Circle),
+    Connector(Connector),
+    Text(Text),
+    Polyline(Polyline),
+    Polygon(Polygon),
+    Curve(Curve),
+    Ellipse(Ellipse),
+    Group(Group),
+}
+
+impl SavedComponent {
+    pub fn load(self) -> Rc<RefCell<dyn Component>> {
+        let component: Rc<RefCell<dyn Component>> = match self {
+            SavedComponent::Triangle(triangle) => Rc::new(RefCell::new(triangle)),
+            SavedComponent::Line(line) => Rc::new(RefCell::new(line)),
+            SavedComponent::Rect(rect) => Rc::new(RefCell::new(rect)),
+            SavedComponent::Circle(circle) => Rc::new(RefCell::new(circle)),
+            SavedComponent::Connector(connector) => Rc::new(RefCell::new(connector)),
+            SavedComponent::Text(text) => Rc::new(RefCell::new(text)),
+            SavedComponent::Polyline(polyline) => Rc::new(RefCell::new(polyline)),
+            SavedComponent::Polygon(polygon) => Rc::new(RefCell::new(polygon)),
+            SavedComponent::Curve(curve) => Rc::new(RefCell::new(curve)),
+            SavedComponent::Ellipse(ellipse) => Rc::new(RefCell::new(ellipse)),
+            SavedComponent::Group(group) => Rc::new(RefCell::new(group)),
+        };
+        component.borrow_mut().set_highlighted(false);
+        component
+    }
+
+    // Reads a snapshot without loading it
+    pub fn get(&self) -> &dyn Component {
+        match self {
+            SavedComponent::Triangle(triangle) => triangle,
+            SavedComponent::Line(line) => line,
+            SavedComponent::Rect(rect) => rect,
+            SavedComponent::Circle(circle) => circle,
+            SavedComponent::Connector(connector) => connector,
+            SavedComponent::Text(text) => text,
+            SavedComponent::Polyline(polyline) => polyline,
+            SavedComponent::Polygon(polygon) => polygon,
+            SavedComponent::Curve(curve) => curve,
+            SavedComponent::Ellipse(ellipse) => ellipse,
+            SavedComponent::Group(group) => group,
         }
     }
 }
 
 pub trait ComponentPreview {
-    fn get_preview(vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>>;
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>>;
 }
 
 pub trait Component {
-    fn get_vertices(&self) -> Vec<f32>;
+    // Curves are flattened until they are within `tolerance` world units of their true shape
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32>;
     fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool;
-    fn set_highlighted(&self, highlighted: bool);
+    fn get_properties(&self) -> &Properties;
+    fn get_properties_mut(&mut self) -> &mut Properties;
+    fn get_handles(&self) -> Vec<Vec2>;
+    fn move_handle(&mut self, handle: usize, position: Vec2);
+    fn get_snap_points(&self) -> Vec<Vec2>;
+    // The point `position` of the way along the outline, where connector endpoints attach. Closed outlines go once
+    // around from 0 to 1, open ones from one end to the other
+    fn get_anchor(&self, position: f32) -> Option<Vec2>;
+    // The position of the point on the outline nearest `point`, if it is within `tolerance`
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32>;
+    fn save(&self) -> SavedComponent;
+    // Takes on the state of a snapshot of the same kind of component, staying highlighted or not
+    fn restore(&mut self, saved: &SavedComponent);
+    fn to_svg(&self) -> String;
+
+    // Only connectors have bindable endpoints; endpoint `i` is moved with handle `i`
+    fn get_bindings(&self) -> Vec<Option<Binding>> {
+        vec![]
+    }
+
+    fn set_binding(&mut self, _endpoint: usize, _binding: Option<Binding>) {}
+
+    // Only groups have children, in the group's own coordinates
+    fn get_children(&self) -> &[Rc<RefCell<dyn Component>>] {
+        &[]
+    }
+
+    fn get_children_mut(&mut self) -> Option<&mut Vec<Rc<RefCell<dyn Component>>>> {
+        None
+    }
+
+    // Guides drawn between handles while the component is selected, like the arms of Bézier control points
+    fn get_handle_lines(&self) -> Vec<(Vec2, Vec2)> {
+        vec![]
+    }
+
+    // Axis-aligned and in world space, covering everything drawn including strokes, markers and labels. None when
+    // nothing is drawn at all
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        get_vertex_bounds(&self.get_vertices(PRECISE_TOLERANCE))
+    }
+
+    // Every style the component is drawn in, which only differs from `get_style` for groups
+    fn get_styles(&self) -> Vec<Style> {
+        vec![self.get_style()]
+    }
+
+    // Changes only what `edit` touches, leaving the rest of the style as it was
+    fn edit_style(&mut self, edit: &mut dyn FnMut(&mut Style)) {
+        let mut style = self.get_style();
+        edit(&mut style);
+        self.set_style(style);
+    }
+
+    fn get_id(&self) -> ComponentId {
+        self.get_properties().id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.get_properties_mut().id = id;
+    }
+
+    fn get_layer(&self) -> LayerId {
+        self.get_properties().layer
+    }
+
+    fn set_layer(&mut self, layer: LayerId) {
+        self.get_properties_mut().layer = layer;
+    }
+
+    fn set_highlighted(&mut self, highlighted: bool) {
+        self.get_properties_mut().highlighted = highlighted;
+    }
+
+    fn get_style(&self) -> Style {
+        self.get_properties().style
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.get_properties_mut().style = style;
+    }
+
+    fn get_label(&self) -> Label {
+        self.get_properties().label.clone()
+    }
+
+    fn set_label(&mut self, label: Label) {
+        self.get_properties_mut().label = label;
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.get_properties().transform
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.get_properties_mut().transform = transform;
+    }
+
+    fn translate(&mut self, amount: Vec2) {
+        self.get_properties_mut().transform.translate(amount);
+    }
+}
+
+// Marquee tests run against the tessellated geometry, so they agree with what is drawn
+pub fn vertices_intersect_rect(vertices: &[f32], min: Vec2, max: Vec2) -> bool {
+    vertices.chunks_exact(3 * VERTEX_SIZE)
+        .any(|triangle| triangle_intersects_rect(
+            [0, VERTEX_SIZE, 2 * VERTEX_SIZE].map(|i| vec2(triangle[i], triangle[i + 1])), min, max))
+}
+
+pub fn vertices_within_rect(vertices: &[f32], min: Vec2, max: Vec2) -> bool {
+    !vertices.is_empty() && vertices
+        .chunks_exact(VERTEX_SIZE)
+        .all(|vertex| (min.x..=max.x).contains(&vertex[0]) && (min.y..=max.y).contains(&vertex[1]))
+}
+
+// Outline positions for shapes made of straight edges. Every edge gets the same share of the positions, so an anchor
+// stays on its edge however the shape is resized
+pub fn get_path_point(points: &[Vec2], closed: bool, position: f32) -> Option<Vec2> {
+    let edges = get_path_edges(points, closed);
+    let Some(last) = edges.len().checked_sub(1) else {
+        return points.first().copied();
+    };
+    let scaled = position.clamp(0.0, 1.0) * edges.len() as f32;
+    let edge = (scaled as usize).min(last);
+    let (from, to) = edges[edge];
+    Some(from + (to - from) * (scaled - edge as f32))
+}
+
+pub fn find_path_position(points: &[Vec2], closed: bool, point: Vec2, tolerance: f32) -> Option<f32> {
+    let edges = get_path_edges(points, closed);
+    if edges.is_empty() {
+        return points.first().filter(|first| (*first - point).magnitude() <= tolerance).map(|_| 0.0);
+    }
+
+    edges.iter()
+        .enumerate()
+        .map(|(edge, (from, to))| {
+            let direction = to - from;
+            let t = match direction.magnitude_squared() {
+                0.0 => 0.0,
+                length_squared => ((point - from).dot(&direction) / length_squared).clamp(0.0, 1.0),
+            };
+            ((edge as f32 + t) / edges.len() as f32, (from + direction * t - point).magnitude())
+        })
+        .filter(|(_, distance)| *distance <= tolerance)
+        .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+        .map(|(position, _)| position)
+}
+
+fn get_path_edges(points: &[Vec2], closed: bool) -> Vec<(Vec2, Vec2)> {
+    let mut edges: Vec<_> = points.windows(2).map(|edge| (edge[0], edge[1])).collect();
+    if closed && points.len() > 2 {
+        edges.push((points[points.len() - 1], points[0]));
+    }
+    edges
+}
+
+pub fn get_vertex_bounds(vertices: &[f32]) -> Option<(Vec2, Vec2)> {
+    vertices.chunks_exact(VERTEX_SIZE)
+        .map(|vertex| (vec2(vertex[0], vertex[1]), vec2(vertex[0], vertex[1])))
+        .reduce(merge_bounds)
+}
+
+pub fn merge_bounds((min1, max1): (Vec2, Vec2), (min2, max2): (Vec2, Vec2)) -> (Vec2, Vec2) {
+    (nalgebra_glm::min2(&min1, &min2), nalgebra_glm::max2(&max1, &max2))
+}
+
+fn add_triangle(vertices: &mut Vec<f32>, v1: Vec2, v2: Vec2, v3: Vec2, color: Color) {
+    for vertex in [v1, v2, v3] {
+        vertices.extend_from_slice(&[vertex.x, vertex.y]);
+        vertices.extend_from_slice(&color);
+    }
+}
+
+// Enough segments for the chords of an arc to stay within `tolerance` of it
+fn get_segment_count(radius: f32, angle: f32, tolerance: f32) -> usize {
+    if radius <= tolerance {
+        return 1;
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((angle / step).ceil() as usize).clamp(1, MAX_ARC_SEGMENTS)
 }
 
 fn sign(v1: Vec2, v2: Vec2, v3: Vec2) -> f32 {
@@ -75,4 +372,23 @@ fn mouse_in_triangle(mouse_pos: Vec2, v1: Vec2, v2: Vec2, v3: Vec2) -> bool {
     let has_positive = (d1 > 0.0) || (d2 > 0.0) || (d3 > 0.0);
 
     !(has_negative && has_positive)
-}
\ No newline at end of file
+}
+
+// Separating axis test against the rect's axes and each triangle edge normal
+fn triangle_intersects_rect(triangle: [Vec2; 3], min: Vec2, max: Vec2) -> bool {
+    let corners = [min, vec2(min.x, max.y), max, vec2(max.x, min.y)];
+    let separated = |axis: Vec2| {
+        let project = |points: &[Vec2]| points.iter()
+            .map(|point| point.dot(&axis))
+            .fold((f32::MAX, f32::MIN), |(low, high), value| (low.min(value), high.max(value)));
+        let (triangle_low, triangle_high) = project(&triangle);
+        let (rect_low, rect_high) = project(&corners);
+        triangle_high < rect_low || rect_high < triangle_low
+    };
+
+    let edge_normals = (0..3).map(|i| {
+        let edge = triangle[(i + 1) % 3] - triangle[i];
+        vec2(-edge.y, edge.x)
+    });
+    ![vec2(1.0, 0.0), vec2(0.0, 1.0)].into_iter().chain(edge_normals).any(separated)
+}
@@ -1,55 +1,128 @@
-use std::f32::consts::PI;
-
 use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
 
-use crate::state::State;
+use crate::svg;
 
-use super::{Component, ComponentPreview};
+use super::{Component, ComponentPreview, SavedComponent, add_triangle, ellipse, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Circle {
+    #[serde(flatten)]
+    properties: Properties,
     center: Vec2,
     radius: f32,
 }
 
 impl Circle {
-    pub fn new(vertices: Vec<Vec2>, state: &State) -> Self {
-        let center = state.world_space_to_screen_space(vertices[0]);
-        let to = state.world_space_to_screen_space(vertices[1]);
-        let radius = (center - to).magnitude();
-        Self { center, radius }
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        let center = vertices[0];
+        let radius = (center - vertices[1]).magnitude();
+        Self { center, radius, properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_perimeter(&self, tolerance: f32) -> Vec<Vec2> {
+        ellipse::get_perimeter(self.center, vec2(self.radius, self.radius), tolerance)
     }
 }
 
 impl ComponentPreview for Circle {
-    fn get_preview(vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>> {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
         match vertices.len() {
-            2 => vec![Box::new(Circle::new(vertices, state))],
+            2 => vec![Box::new(Circle::new(vertices, style))],
             _ => vec![],
         }
     }
 }
 
 impl Component for Circle {
-    fn get_vertices(&self) -> Vec<f32> {
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        let tolerance = self.properties.transform.get_local_tolerance(tolerance);
         let mut vertices = vec![];
-        let mut previous_position = self.center + vec2(self.radius, 0.0);
-        let circumference = 2.0 * PI * self.radius;
-        let sides = (circumference / 4.0) as i32;
-        for i in 0..=sides {
-            let angle = (i as f32 / sides as f32) * 2.0 * PI;
-            let new_position = self.center + vec2(self.radius * f32::sin(angle), self.radius * f32::cos(angle));
-            vertices.push(self.center.x);
-            vertices.push(self.center.y);
-            vertices.push(previous_position.x);
-            vertices.push(previous_position.y);
-            vertices.push(new_position.x);
-            vertices.push(new_position.y);
-            previous_position = new_position;
+        let perimeter = self.get_perimeter(tolerance);
+        if let Some(fill) = self.properties.style.get_fill(self.properties.highlighted) {
+            for (i, from) in perimeter.iter().enumerate() {
+                add_triangle(&mut vertices, self.center, *from, perimeter[(i + 1) % perimeter.len()], fill);
+            }
+        }
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            stroke::add_stroke(&mut vertices, &perimeter, true, self.properties.style.stroke_width, self.properties.style.join, stroke);
         }
-        vertices
+        self.properties.label.add_vertices(&mut vertices, self.center, self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
     }
 
     fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
-        (self.center - mouse_pos).magnitude() < self.radius
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let distance = (self.center - mouse_pos).magnitude();
+        (self.properties.style.filled && distance < self.radius)
+            || (distance - self.radius).abs() <= self.properties.style.stroke_width / 2.0
+            || self.properties.label.contains(self.center, mouse_pos)
+    }
+
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        let mut extent = self.properties.label.get_extent(self.center);
+        if self.properties.style.is_drawn() {
+            extent.push((self.center, self.radius + self.properties.style.stroke_width / 2.0));
+        }
+        self.properties.transform.get_bounds(&extent)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        vec![self.properties.transform.apply(self.center + vec2(self.radius, 0.0))]
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.radius = (position - self.center).magnitude(),
+            _ => panic!("Circle has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![
+            self.center + vec2(self.radius, 0.0),
+            self.center + vec2(0.0, self.radius),
+            self.center - vec2(self.radius, 0.0),
+            self.center - vec2(0.0, self.radius),
+            self.center,
+        ])
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        Some(self.properties.transform.apply(ellipse::get_perimeter_point(self.center, vec2(self.radius, self.radius), position)))
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        let position = ellipse::get_perimeter_position(self.center, vec2(self.radius, self.radius), self.properties.transform.invert(point));
+        ((self.get_anchor(position)? - point).magnitude() <= tolerance).then_some(position)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Circle(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Circle(circle) = saved else {
+            panic!("Circle can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = circle.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let center = svg::point(self.center);
+        let circle = format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\"{}/>",
+            center.x, center.y, self.radius, svg::style_attributes(&self.properties.style));
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(circle, self.center, &self.properties.style))
     }
 }
@@ -0,0 +1,89 @@
+use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
+
+use super::{Component, ComponentPreview, SavedComponent, Binding, line::Line, style::Style, properties::Properties};
+
+// A line whose endpoints follow the anchors they are bound to; the component layer re-resolves them after every edit
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Connector {
+    line: Line,
+    bindings: [Option<Binding>; 2],
+}
+
+impl Connector {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { line: Line::new(vertices, style), bindings: [None, None] }
+    }
+}
+
+impl ComponentPreview for Connector {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            2 => vec![Box::new(Connector::new(vertices, style))],
+            _ => vec![],
+        }
+    }
+}
+
+impl Component for Connector {
+    fn get_properties(&self) -> &Properties {
+        self.line.get_properties()
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        self.line.get_properties_mut()
+    }
+
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        self.line.get_vertices(tolerance)
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        self.line.hitbox_intersects(mouse_pos)
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.line.get_handles()
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        self.line.move_handle(handle, position);
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.line.get_snap_points()
+    }
+
+    // Connecting connectors to each other would let bindings form cycles
+    fn get_anchor(&self, _position: f32) -> Option<Vec2> {
+        None
+    }
+
+    fn find_anchor(&self, _point: Vec2, _tolerance: f32) -> Option<f32> {
+        None
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Connector(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Connector(connector) = saved else {
+            panic!("Connector can't be restored from a different kind of component");
+        };
+        self.line.restore(&SavedComponent::Line(connector.line.clone()));
+        self.bindings = connector.bindings;
+    }
+
+    fn to_svg(&self) -> String {
+        self.line.to_svg()
+    }
+
+    fn get_bindings(&self) -> Vec<Option<Binding>> {
+        self.bindings.to_vec()
+    }
+
+    fn set_binding(&mut self, endpoint: usize, binding: Option<Binding>) {
+        self.bindings[endpoint] = binding;
+    }
+}
@@ -0,0 +1,270 @@
+use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{Component, ComponentPreview, SavedComponent, PRECISE_TOLERANCE, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
+
+// Subdivision stops here even if a degenerate segment never looks flat
+const MAX_SUBDIVISIONS: u32 = 16;
+const TRIM_STEPS: u32 = 24;
+// Points along each segment checked for the nearest one before narrowing it down
+const NEAREST_SAMPLES: u32 = 16;
+
+// A chain of cubic Bézier segments
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Curve {
+    #[serde(flatten)]
+    properties: Properties,
+    // Every third point is an anchor on the curve, with the two control points of each segment between them
+    points: Vec<Vec2>,
+}
+
+fn get_segments(points: &[Vec2]) -> impl Iterator<Item = [Vec2; 4]> + '_ {
+    points.windows(4).step_by(3).map(|segment| [segment[0], segment[1], segment[2], segment[3]])
+}
+
+// De Casteljau subdivision at `t`
+fn split(segment: [Vec2; 4], t: f32) -> ([Vec2; 4], [Vec2; 4]) {
+    let lerp = |from: Vec2, to: Vec2| from + (to - from) * t;
+    let [p0, p1, p2, p3] = segment;
+    let (a, b, c) = (lerp(p0, p1), lerp(p1, p2), lerp(p2, p3));
+    let (d, e) = (lerp(a, b), lerp(b, c));
+    let f = lerp(d, e);
+    ([p0, a, d, f], [f, e, c, p3])
+}
+
+// The curve stays within its control polygon, so it is flat enough once both control points are near the chord
+fn flatten_segment(segment: [Vec2; 4], tolerance: f32, depth: u32, points: &mut Vec<Vec2>) {
+    let flat = [segment[1], segment[2]].iter()
+        .all(|control| stroke::distance_to_segment(*control, segment[0], segment[3]) <= tolerance);
+    if flat || depth == MAX_SUBDIVISIONS {
+        points.push(segment[3]);
+        return;
+    }
+
+    let (first, second) = split(segment, 0.5);
+    flatten_segment(first, tolerance, depth + 1, points);
+    flatten_segment(second, tolerance, depth + 1, points);
+}
+
+fn flatten(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let mut flattened = vec![points[0]];
+    for segment in get_segments(points) {
+        flatten_segment(segment, tolerance, 0, &mut flattened);
+    }
+    flattened
+}
+
+// The Bézier parameter of the point on the segment nearest `point`, and how far away it is
+fn find_nearest(segment: [Vec2; 4], point: Vec2) -> (f32, f32) {
+    let distance = |t: f32| (split(segment, t).0[3] - point).magnitude();
+    let closer = |t1: &f32, t2: &f32| distance(*t1).total_cmp(&distance(*t2));
+    let mut step = 1.0 / NEAREST_SAMPLES as f32;
+    let mut t = (0..=NEAREST_SAMPLES).map(|i| i as f32 * step).min_by(closer).unwrap_or(0.0);
+    for _ in 0..TRIM_STEPS {
+        step /= 2.0;
+        t = [t - step, t, t + step].into_iter().map(|t| t.clamp(0.0, 1.0)).min_by(closer).unwrap_or(t);
+    }
+    (t, distance(t))
+}
+
+// Cuts the curve where it leaves a circle of `radius` around its first point, which is where a marker's back
+// edge meets it. Empty if the curve never leaves the circle
+fn trim_start(points: &[Vec2], radius: f32) -> Vec<Vec2> {
+    if radius <= 0.0 {
+        return points.to_vec();
+    }
+
+    let tip = points[0];
+    let Some((index, segment)) = get_segments(points).enumerate().find(|(_, segment)| (segment[3] - tip).magnitude() > radius) else {
+        return vec![];
+    };
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..TRIM_STEPS {
+        let middle = (low + high) / 2.0;
+        if (split(segment, middle).0[3] - tip).magnitude() > radius {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    let (_, rest) = split(segment, high);
+    rest.iter().chain(&points[3 * index + 4..]).copied().collect()
+}
+
+impl Curve {
+    // Control points follow Catmull-Rom tangents, so the curve passes smoothly through every click
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        let last = vertices.len() - 1;
+        let tangent = |i: usize| {
+            let (previous, next) = (i.saturating_sub(1), (i + 1).min(last));
+            (vertices[next] - vertices[previous]) / (next - previous) as f32
+        };
+
+        let mut points = vec![];
+        for i in 0..last {
+            points.push(vertices[i]);
+            points.push(vertices[i] + tangent(i) / 3.0);
+            points.push(vertices[i + 1] - tangent(i + 1) / 3.0);
+        }
+        points.push(vertices[last]);
+        Self { points, properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_reversed(&self) -> Vec<Vec2> {
+        self.points.iter().rev().copied().collect()
+    }
+
+    // Marker directions point out of the curve along its tangent at each end
+    fn get_directions(&self) -> (Vec2, Vec2) {
+        (-stroke::get_start_direction(&self.points), -stroke::get_start_direction(&self.get_reversed()))
+    }
+
+    fn get_midpoint(&self) -> Vec2 {
+        let flattened = flatten(&self.points, PRECISE_TOLERANCE);
+        let length = stroke::get_length(&flattened);
+        stroke::trim_start(flattened, length / 2.0)[0]
+    }
+
+    // The control points of the part of the curve between the markers, if they leave any
+    fn get_shaft(&self) -> Option<Vec<Vec2>> {
+        let start_inset = self.properties.style.start_marker.get_inset(self.properties.style.stroke_width);
+        let end_inset = self.properties.style.end_marker.get_inset(self.properties.style.stroke_width);
+        let mut points = trim_start(&self.get_reversed(), end_inset);
+        if points.is_empty() {
+            return None;
+        }
+        points.reverse();
+        Some(trim_start(&points, start_inset)).filter(|points| !points.is_empty())
+    }
+}
+
+impl ComponentPreview for Curve {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            0 | 1 => vec![],
+            _ => vec![Box::new(Curve::new(vertices, style))],
+        }
+    }
+}
+
+impl Component for Curve {
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        let tolerance = self.properties.transform.get_local_tolerance(tolerance);
+        let mut vertices = vec![];
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            if let Some(points) = self.get_shaft() {
+                stroke::add_stroke(&mut vertices, &flatten(&points, tolerance), false, self.properties.style.stroke_width, self.properties.style.join, stroke);
+            }
+            let (start_direction, end_direction) = self.get_directions();
+            self.properties.style.start_marker.add_vertices(&mut vertices, self.points[0], start_direction, &self.properties.style, stroke);
+            self.properties.style.end_marker.add_vertices(&mut vertices, self.points[self.points.len() - 1], end_direction, &self.properties.style, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, self.get_midpoint(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    // Distance to the curve is measured against a flattening fine enough to be exact at any practical zoom
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let (start_direction, end_direction) = self.get_directions();
+        stroke::stroke_contains(&flatten(&self.points, PRECISE_TOLERANCE), false, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.style.start_marker.contains(self.points[0], start_direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.style.end_marker.contains(self.points[self.points.len() - 1], end_direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.label.contains(self.get_midpoint(), mouse_pos)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.points.clone())
+    }
+
+    // Anchors carry their control points with them, so the curve keeps its shape around them
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        let Some(point) = self.points.get(handle) else {
+            panic!("Curve has no handle {}", handle);
+        };
+        let delta = position - point;
+        if handle.is_multiple_of(3) {
+            for control in [handle.checked_sub(1), Some(handle + 1)].into_iter().flatten() {
+                if let Some(control) = self.points.get_mut(control) {
+                    *control += delta;
+                }
+            }
+        }
+        self.points[handle] = position;
+    }
+
+    fn get_handle_lines(&self) -> Vec<(Vec2, Vec2)> {
+        get_segments(&self.points)
+            .flat_map(|[p0, p1, p2, p3]| [(p0, p1), (p3, p2)])
+            .map(|(from, to)| (self.properties.transform.apply(from), self.properties.transform.apply(to)))
+            .collect()
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.points.iter().step_by(3).copied().collect())
+    }
+
+    // Every segment gets the same share of the positions, like the edges of a polyline
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        let segments: Vec<_> = get_segments(&self.points).collect();
+        let last = segments.len().checked_sub(1)?;
+        let scaled = position.clamp(0.0, 1.0) * segments.len() as f32;
+        let segment = (scaled as usize).min(last);
+        Some(self.properties.transform.apply(split(segments[segment], scaled - segment as f32).0[3]))
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        let local = self.properties.transform.invert(point);
+        let segments: Vec<_> = get_segments(&self.points).collect();
+        let (segment, (t, _)) = segments.iter()
+            .map(|segment| find_nearest(*segment, local))
+            .enumerate()
+            .min_by(|(_, (_, distance1)), (_, (_, distance2))| distance1.total_cmp(distance2))?;
+        let position = (segment as f32 + t) / segments.len() as f32;
+        ((self.get_anchor(position)? - point).magnitude() <= tolerance).then_some(position)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Curve(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Curve(curve) = saved else {
+            panic!("Curve can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = curve.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let shaft = self.get_shaft().map(|points| {
+            let start = svg::point(points[0]);
+            let segments: String = get_segments(&points)
+                .map(|[_, p1, p2, p3]| format!(" C {}", svg::points(&[p1, p2, p3])))
+                .collect();
+            format!("<path d=\"M {},{}{}\" fill=\"none\"{}/>", start.x, start.y, segments, svg::stroke_attributes(&self.properties.style))
+        });
+        let (start_direction, end_direction) = self.get_directions();
+        let elements: Vec<String> = shaft.into_iter()
+            .chain(self.properties.style.start_marker.to_svg(self.points[0], start_direction, &self.properties.style))
+            .chain(self.properties.style.end_marker.to_svg(self.points[self.points.len() - 1], end_direction, &self.properties.style))
+            .collect();
+        let curve = match elements.as_slice() {
+            [curve] => curve.clone(),
+            _ => format!("<g>{}</g>", elements.concat()),
+        };
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(curve, self.get_midpoint(), &self.properties.style))
+    }
+}
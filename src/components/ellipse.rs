@@ -0,0 +1,252 @@
+use std::f32::consts::{PI, TAU};
+
+use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{Component, ComponentPreview, SavedComponent, add_triangle, get_segment_count, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ellipse {
+    #[serde(flatten)]
+    properties: Properties,
+    from: Vec2,
+    to: Vec2,
+}
+
+// Positions go once around from the rightmost point, through the one with the greatest y
+pub fn get_perimeter_point(center: Vec2, radii: Vec2, position: f32) -> Vec2 {
+    let angle = position * TAU;
+    center + vec2(radii.x * angle.cos(), radii.y * angle.sin())
+}
+
+// The position of the point on the perimeter nearest `point`. NaN for a flat ellipse
+pub fn get_perimeter_position(center: Vec2, radii: Vec2, point: Vec2) -> f32 {
+    let parameter = get_nearest_point(point - center, radii).component_div(&radii);
+    (parameter.y.atan2(parameter.x) / TAU).rem_euclid(1.0)
+}
+
+pub fn get_perimeter(center: Vec2, radii: Vec2, tolerance: f32) -> Vec<Vec2> {
+    let sides = get_segment_count(radii.x.max(radii.y), 2.0 * PI, tolerance).max(3);
+    (0..sides)
+        .map(|i| (i as f32 / sides as f32) * 2.0 * PI)
+        .map(|angle| center + vec2(radii.x * f32::sin(angle), radii.y * f32::cos(angle)))
+        .collect()
+}
+
+// The point on the ellipse nearest `point`, both relative to its centre. Solved in the first quadrant, which is enough
+// by symmetry, with the larger radius along x, following Eberly's "Distance from a Point to an Ellipse"
+fn get_nearest_point(point: Vec2, radii: Vec2) -> Vec2 {
+    if radii.x <= 0.0 || radii.y <= 0.0 {
+        let t = (point.x * radii.x + point.y * radii.y) / radii.magnitude_squared().max(f32::MIN_POSITIVE);
+        return radii * t.clamp(-1.0, 1.0);
+    }
+    if radii.y > radii.x {
+        let nearest = get_nearest_point(vec2(point.y, point.x), vec2(radii.y, radii.x));
+        return vec2(nearest.y, nearest.x);
+    }
+
+    let (e0, e1) = (radii.x as f64, radii.y as f64);
+    let (y0, y1) = (point.x.abs() as f64, point.y.abs() as f64);
+    let (x0, x1) = if y1 > 0.0 && y0 > 0.0 {
+        let r0 = (e0 / e1).powi(2);
+        let s = find_nearest_root(r0, y0 / e0, y1 / e1);
+        (r0 * y0 / (s + r0), y1 / (s + 1.0))
+    } else if y1 > 0.0 {
+        (0.0, e1)
+    } else if e0 * y0 < e0 * e0 - e1 * e1 {
+        // On the major axis inside the evolute, where the nearest points are off the axis
+        let x = e0 * y0 / (e0 * e0 - e1 * e1);
+        (e0 * x, e1 * (1.0 - x * x).sqrt())
+    } else {
+        (e0, 0.0)
+    };
+    vec2((x0 as f32).copysign(point.x), (x1 as f32).copysign(point.y))
+}
+
+// Bisects for the root of the distance equation until the bracket can't get any smaller
+fn find_nearest_root(r0: f64, z0: f64, z1: f64) -> f64 {
+    let n0 = r0 * z0;
+    let g = z0 * z0 + z1 * z1 - 1.0;
+    if g == 0.0 {
+        return 0.0;
+    }
+
+    let (mut s0, mut s1) = (z1 - 1.0, if g < 0.0 { 0.0 } else { n0.hypot(z1) - 1.0 });
+    loop {
+        let s = (s0 + s1) / 2.0;
+        if s == s0 || s == s1 {
+            return s;
+        }
+        let g = (n0 / (s + r0)).powi(2) + (z1 / (s + 1.0)).powi(2) - 1.0;
+        if g > 0.0 {
+            s0 = s;
+        } else if g < 0.0 {
+            s1 = s;
+        } else {
+            return s;
+        }
+    }
+}
+
+fn distance_to_perimeter(point: Vec2, radii: Vec2) -> f32 {
+    (point - get_nearest_point(point, radii)).magnitude()
+}
+
+impl Ellipse {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { from: vertices[0], to: vertices[1], properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_center(&self) -> Vec2 {
+        (self.from + self.to) / 2.0
+    }
+
+    fn get_radii(&self) -> Vec2 {
+        nalgebra_glm::abs(&(self.to - self.from)) / 2.0
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        let radii = self.get_radii();
+        radii.x > 0.0 && radii.y > 0.0
+            && (point - self.get_center()).component_div(&radii).magnitude_squared() <= 1.0
+    }
+}
+
+impl ComponentPreview for Ellipse {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            2 => vec![Box::new(Ellipse::new(vertices, style))],
+            _ => vec![],
+        }
+    }
+}
+
+impl Component for Ellipse {
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        let tolerance = self.properties.transform.get_local_tolerance(tolerance);
+        let mut vertices = vec![];
+        let center = self.get_center();
+        let perimeter = get_perimeter(center, self.get_radii(), tolerance);
+        if let Some(fill) = self.properties.style.get_fill(self.properties.highlighted) {
+            for (i, from) in perimeter.iter().enumerate() {
+                add_triangle(&mut vertices, center, *from, perimeter[(i + 1) % perimeter.len()], fill);
+            }
+        }
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            stroke::add_stroke(&mut vertices, &perimeter, true, self.properties.style.stroke_width, self.properties.style.join, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, center, self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        (self.properties.style.filled && self.contains(mouse_pos))
+            || distance_to_perimeter(mouse_pos - self.get_center(), self.get_radii()) <= self.properties.style.stroke_width / 2.0
+            || self.properties.label.contains(self.get_center(), mouse_pos)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    // The corners of the bounding box, as for a rect
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![
+            vec2(self.from.x, self.from.y),
+            vec2(self.from.x, self.to.y),
+            vec2(self.to.x, self.to.y),
+            vec2(self.to.x, self.from.y),
+        ])
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.from = position,
+            1 => (self.from.x, self.to.y) = (position.x, position.y),
+            2 => self.to = position,
+            3 => (self.to.x, self.from.y) = (position.x, position.y),
+            _ => panic!("Ellipse has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        let (center, radii) = (self.get_center(), self.get_radii());
+        self.properties.transform.apply_all(vec![
+            center + vec2(radii.x, 0.0),
+            center + vec2(0.0, radii.y),
+            center - vec2(radii.x, 0.0),
+            center - vec2(0.0, radii.y),
+            center,
+        ])
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        Some(self.properties.transform.apply(get_perimeter_point(self.get_center(), self.get_radii(), position)))
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        let position = get_perimeter_position(self.get_center(), self.get_radii(), self.properties.transform.invert(point));
+        ((self.get_anchor(position)? - point).magnitude() <= tolerance).then_some(position)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Ellipse(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Ellipse(ellipse) = saved else {
+            panic!("Ellipse can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = ellipse.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let (center, radii) = (svg::point(self.get_center()), self.get_radii());
+        let ellipse = format!("<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"{}/>",
+            center.x, center.y, radii.x, radii.y, svg::style_attributes(&self.properties.style));
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(ellipse, self.get_center(), &self.properties.style))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The nearest of many points spaced evenly by angle, as a reference
+    fn sample_distance(point: Vec2, radii: Vec2) -> f32 {
+        (0..20_000)
+            .map(|i| get_perimeter_point(vec2(0.0, 0.0), radii, i as f32 / 20_000.0))
+            .map(|perimeter| (perimeter - point).magnitude())
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn distances_to_eccentric_perimeters_are_exact() {
+        for radii in [vec2(100.0, 2.0), vec2(3.0, 80.0), vec2(50.0, 50.0)] {
+            for point in [vec2(10.0, 1.0), vec2(-90.0, 30.0), vec2(1.0, -60.0), vec2(0.0, 0.0), vec2(120.0, 0.0), vec2(20.0, 0.0)] {
+                let distance = distance_to_perimeter(point, radii);
+                assert!(distance <= sample_distance(point, radii) + 1e-3, "{:?} from {:?}", point, radii);
+                assert!(distance >= sample_distance(point, radii) - 0.05, "{:?} from {:?}", point, radii);
+            }
+        }
+    }
+
+    #[test]
+    fn perimeter_positions_find_the_nearest_point() {
+        let radii = vec2(100.0, 10.0);
+        let point = vec2(60.0, 20.0);
+        let position = get_perimeter_position(vec2(0.0, 0.0), radii, point);
+        let nearest = get_perimeter_point(vec2(0.0, 0.0), radii, position);
+        assert!(((nearest - point).magnitude() - sample_distance(point, radii)).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::OnceLock};
+
+use lyon_tessellation::{FillOptions, FillTessellator, VertexBuffers, geometry_builder::simple_builder, math::point, path::{Path, path::Builder}};
+use nalgebra_glm::{Vec2, vec2};
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+pub const FONT_DATA: &[u8] = include_bytes!("../../resources/fonts/Inter-Regular.otf");
+// Glyphs are tessellated once at a fixed tolerance and scaled, so this is relative to the em square
+const TOLERANCE_EM: f32 = 0.002;
+
+thread_local! {
+    static GLYPHS: RefCell<HashMap<GlyphId, Rc<Vec<Vec2>>>> = RefCell::new(HashMap::new());
+}
+
+fn get_face() -> &'static Face<'static> {
+    static FACE: OnceLock<Face<'static>> = OnceLock::new();
+    FACE.get_or_init(|| Face::parse(FONT_DATA, 0).expect("Failed to parse bundled font"))
+}
+
+fn get_units_per_em() -> f32 {
+    get_face().units_per_em() as f32
+}
+
+// Heights are in ems, measured up from the baseline
+pub fn get_ascender() -> f32 {
+    get_face().ascender() as f32 / get_units_per_em()
+}
+
+pub fn get_line_height() -> f32 {
+    let face = get_face();
+    (face.ascender() - face.descender() + face.line_gap()) as f32 / get_units_per_em()
+}
+
+struct PathOutline {
+    builder: Builder,
+    open: bool,
+}
+
+impl OutlineBuilder for PathOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if self.open {
+            self.builder.end(true);
+        }
+        self.builder.begin(point(x, y));
+        self.open = true;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.builder.line_to(point(x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.builder.quadratic_bezier_to(point(x1, y1), point(x, y));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.builder.cubic_bezier_to(point(x1, y1), point(x2, y2), point(x, y));
+    }
+
+    fn close(&mut self) {
+        self.builder.end(true);
+        self.open = false;
+    }
+}
+
+fn tessellate_glyph(glyph: GlyphId) -> Vec<Vec2> {
+    let mut outline = PathOutline { builder: Path::builder(), open: false };
+    if get_face().outline_glyph(glyph, &mut outline).is_none() {
+        return vec![];
+    }
+    if outline.open {
+        outline.builder.end(true);
+    }
+
+    let units_per_em = get_units_per_em();
+    let mut buffers = VertexBuffers::new();
+    let options = FillOptions::non_zero().with_tolerance(TOLERANCE_EM * units_per_em);
+    if FillTessellator::new().tessellate_path(&outline.builder.build(), &options, &mut simple_builder(&mut buffers)).is_err() {
+        return vec![];
+    }
+    buffers.indices.iter()
+        .map(|index| buffers.vertices[*index as usize])
+        .map(|vertex| vec2(vertex.x, vertex.y) / units_per_em)
+        .collect()
+}
+
+// Triangle list for the glyph, in ems with the origin on the baseline
+pub fn get_glyph_triangles(glyph: GlyphId) -> Rc<Vec<Vec2>> {
+    GLYPHS.with(|glyphs| glyphs.borrow_mut()
+        .entry(glyph)
+        .or_insert_with(|| Rc::new(tessellate_glyph(glyph)))
+        .clone())
+}
+
+// Glyphs with their horizontal offsets in ems, and the total advance of the line
+pub fn shape_line(line: &str) -> (Vec<(GlyphId, f32)>, f32) {
+    let face = get_face();
+    let mut glyphs = vec![];
+    let mut advance = 0.0;
+    for character in line.chars() {
+        let glyph = face.glyph_index(character).unwrap_or(GlyphId(0));
+        glyphs.push((glyph, advance));
+        advance += face.glyph_hor_advance(glyph).unwrap_or(0) as f32 / get_units_per_em();
+    }
+    (glyphs, advance)
+}
@@ -0,0 +1,178 @@
+use std::{cell::RefCell, rc::Rc};
+
+use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+use super::{Component, SavedComponent, merge_bounds, transform::Transform, text::Label, style::Style, properties::Properties};
+
+// Scaling a group flat would lose the children's shape for good
+const MIN_SCALE_FACTOR: f32 = 0.001;
+
+// Components that select, move and transform as one. Children keep their own transforms, inside the group's, and
+// are live components so they can be selected and edited in place while the group is open. Only the label is drawn
+// in the group's own style
+#[derive(Serialize, Deserialize)]
+pub struct Group {
+    #[serde(flatten)]
+    properties: Properties,
+    #[serde(serialize_with = "save_children", deserialize_with = "load_children")]
+    children: Vec<Rc<RefCell<dyn Component>>>,
+}
+
+fn save_children<S: Serializer>(children: &[Rc<RefCell<dyn Component>>], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(children.iter().map(|child| child.borrow().save()))
+}
+
+fn load_children<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Rc<RefCell<dyn Component>>>, D::Error> {
+    Ok(Vec::<SavedComponent>::deserialize(deserializer)?.into_iter().map(SavedComponent::load).collect())
+}
+
+// Copies the children too, so snapshots never share them with the live group
+impl Clone for Group {
+    fn clone(&self) -> Self {
+        Self {
+            properties: self.properties.clone(),
+            children: self.children.iter().map(|child| child.borrow().save().load()).collect(),
+        }
+    }
+}
+
+impl Group {
+    pub fn new(children: Vec<Rc<RefCell<dyn Component>>>, style: Style, label: Label, transform: Transform) -> Self {
+        Self { properties: Properties::new(style, label, transform), children }
+    }
+
+    // In the group's own coordinates
+    fn get_local_bounds(&self) -> (Vec2, Vec2) {
+        self.children.iter()
+            .filter_map(|child| child.borrow().get_bounds())
+            .reduce(merge_bounds)
+            .unwrap_or((vec2(0.0, 0.0), vec2(0.0, 0.0)))
+    }
+
+    fn get_corners(&self) -> Vec<Vec2> {
+        let (min, max) = self.get_local_bounds();
+        vec![min, vec2(min.x, max.y), max, vec2(max.x, min.y)]
+    }
+
+    fn get_center(&self) -> Vec2 {
+        let (min, max) = self.get_local_bounds();
+        (min + max) / 2.0
+    }
+}
+
+impl Component for Group {
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        let tolerance = self.properties.transform.get_local_tolerance(tolerance);
+        let mut vertices: Vec<f32> = self.children.iter().flat_map(|child| child.borrow().get_vertices(tolerance)).collect();
+        self.properties.label.add_vertices(&mut vertices, self.get_center(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        self.children.iter().any(|child| child.borrow().hitbox_intersects(mouse_pos))
+            || self.properties.label.contains(self.get_center(), mouse_pos)
+    }
+
+    fn set_highlighted(&mut self, highlighted: bool) {
+        self.properties.highlighted = highlighted;
+        for child in &self.children {
+            child.borrow_mut().set_highlighted(highlighted);
+        }
+    }
+
+    // The label's, if there is one, and the children's
+    fn get_styles(&self) -> Vec<Style> {
+        let label = Some(self.properties.style).filter(|_| !self.properties.label.text.is_empty());
+        label.into_iter().chain(self.children.iter().flat_map(|child| child.borrow().get_styles())).collect()
+    }
+
+    fn edit_style(&mut self, edit: &mut dyn FnMut(&mut Style)) {
+        edit(&mut self.properties.style);
+        for child in &self.children {
+            child.borrow_mut().edit_style(edit);
+        }
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    // The corners of the children's bounds, which scale the whole group about the opposite corner
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.get_corners())
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let corners = self.get_corners();
+        let (Some(corner), Some(opposite)) = (corners.get(handle), corners.get((handle + 2) % corners.len())) else {
+            panic!("Group has no handle {}", handle);
+        };
+        let size = corner - opposite;
+        let target = self.properties.transform.invert(position) - opposite;
+        let factor = vec2(target.x / size.x, target.y / size.y)
+            .map(|factor| if factor.is_finite() && factor.abs() >= MIN_SCALE_FACTOR { factor } else { 1.0 });
+        self.properties.transform.scale_about(factor, *opposite);
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.children.iter().flat_map(|child| child.borrow().get_snap_points()).collect())
+    }
+
+    // Connectors bind to the children instead
+    fn get_anchor(&self, _position: f32) -> Option<Vec2> {
+        None
+    }
+
+    fn find_anchor(&self, _point: Vec2, _tolerance: f32) -> Option<f32> {
+        None
+    }
+
+    fn get_children(&self) -> &[Rc<RefCell<dyn Component>>] {
+        &self.children
+    }
+
+    fn get_children_mut(&mut self) -> Option<&mut Vec<Rc<RefCell<dyn Component>>>> {
+        Some(&mut self.children)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Group(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Group(group) = saved else {
+            panic!("Group can't be restored from a different kind of component");
+        };
+        // Children that are still there are restored in place, so they stay selected inside an open group
+        let children = group.children.iter().map(|saved_child| {
+            let saved_child = saved_child.borrow().save();
+            let id = saved_child.get().get_id();
+            match self.children.iter().find(|child| child.borrow().get_id() == id) {
+                Some(child) => {
+                    child.borrow_mut().restore(&saved_child);
+                    child.clone()
+                }
+                None => {
+                    let child = saved_child.load();
+                    child.borrow_mut().set_highlighted(self.properties.highlighted);
+                    child
+                }
+            }
+        }).collect();
+        let highlighted = self.properties.highlighted;
+        *self = Self { properties: group.properties.clone(), children };
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let children: String = self.children.iter().map(|child| child.borrow().to_svg()).collect();
+        let group = format!("<g>{}</g>", children);
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(group, self.get_center(), &self.properties.style))
+    }
+}
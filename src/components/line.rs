@@ -1,56 +1,151 @@
 use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
 
-use crate::state::State;
+use crate::svg;
 
-use super::{Component, ComponentPreview, mouse_in_triangle};
+use super::{Component, ComponentPreview, SavedComponent, get_path_point, find_path_position, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Line {
+    #[serde(flatten)]
+    properties: Properties,
     from: Vec2,
     to: Vec2,
 }
 
 impl Line {
-    pub fn new(vertices: Vec<Vec2>, state: &State) -> Self {
-        let from = state.world_space_to_screen_space(vertices[0]);
-        let to = state.world_space_to_screen_space(vertices[1]);
-        Self { from, to }
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { from: vertices[0], to: vertices[1], properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_direction(&self) -> Vec2 {
+        if self.from == self.to {
+            vec2(1.0, 0.0)
+        } else {
+            (self.to - self.from).normalize()
+        }
+    }
+
+    // The visible part of the line between the markers, if they leave any
+    fn get_shaft(&self) -> Option<(Vec2, Vec2)> {
+        let start_inset = self.properties.style.start_marker.get_inset(self.properties.style.stroke_width);
+        let end_inset = self.properties.style.end_marker.get_inset(self.properties.style.stroke_width);
+        if start_inset + end_inset >= (self.to - self.from).magnitude() && start_inset + end_inset > 0.0 {
+            return None;
+        }
+
+        let direction = self.get_direction();
+        Some((self.from + direction * start_inset, self.to - direction * end_inset))
     }
 }
 
 impl ComponentPreview for Line {
-    fn get_preview(vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>> {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
         match vertices.len() {
-            2 => vec![Box::new(Line::new(vertices, state))],
+            2 => vec![Box::new(Line::new(vertices, style))],
             _ => vec![],
         }
     }
 }
 
 impl Component for Line {
-    fn get_vertices(&self,) -> Vec<f32> {
-        let direction = self.from - self.to;
-        let perpendicular_unit = vec2(-direction.y, direction.x).normalize();
-        let v1 = self.from + perpendicular_unit;
-        let v2 = self.from - perpendicular_unit;
-        let v3 = self.to + perpendicular_unit;
-        let v4 = self.to - perpendicular_unit;
-        vec![
-            v1.x, v1.y,
-            v2.x, v2.y,
-            v3.x, v3.y,
-            v2.x, v2.y,
-            v3.x, v3.y,
-            v4.x, v4.y,
-        ]
+    fn get_vertices(&self, _tolerance: f32) -> Vec<f32> {
+        let mut vertices = vec![];
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            if let Some((from, to)) = self.get_shaft() {
+                stroke::add_stroke(&mut vertices, &[from, to], false, self.properties.style.stroke_width, self.properties.style.join, stroke);
+            }
+            let direction = self.get_direction();
+            self.properties.style.start_marker.add_vertices(&mut vertices, self.from, -direction, &self.properties.style, stroke);
+            self.properties.style.end_marker.add_vertices(&mut vertices, self.to, direction, &self.properties.style, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, (self.from + self.to) / 2.0, self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
     }
 
     fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
-        let direction = self.from - self.to;
-        let perpendicular_unit = vec2(-direction.y, direction.x).normalize();
-        let v1 = self.from + perpendicular_unit;
-        let v2 = self.from - perpendicular_unit;
-        let v3 = self.to + perpendicular_unit;
-        let v4 = self.to - perpendicular_unit;
-        mouse_in_triangle(mouse_pos, v1, v2, v3) && mouse_in_triangle(mouse_pos, v2, v3, v4)
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let direction = self.get_direction();
+        stroke::distance_to_segment(mouse_pos, self.from, self.to) <= self.properties.style.stroke_width / 2.0
+            || self.properties.style.start_marker.contains(self.from, -direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.style.end_marker.contains(self.to, direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.label.contains((self.from + self.to) / 2.0, mouse_pos)
+    }
+
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        let mut extent = self.properties.label.get_extent((self.from + self.to) / 2.0);
+        if self.properties.style.get_stroke(false).is_some() {
+            if let Some((from, to)) = self.get_shaft() {
+                extent.extend(stroke::get_extent(&[from, to], false, self.properties.style.stroke_width, self.properties.style.join));
+            }
+            let direction = self.get_direction();
+            extent.extend(self.properties.style.start_marker.get_extent(self.from, -direction, &self.properties.style));
+            extent.extend(self.properties.style.end_marker.get_extent(self.to, direction, &self.properties.style));
+        }
+        self.properties.transform.get_bounds(&extent)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![self.from, self.to])
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.from = position,
+            1 => self.to = position,
+            _ => panic!("Line has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![self.from, self.to, (self.from + self.to) / 2.0])
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_handles(), false, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_handles(), false, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Line(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Line(line) = saved else {
+            panic!("Line can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = line.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let shaft = self.get_shaft().map(|(from, to)| {
+            let (from, to) = (svg::point(from), svg::point(to));
+            format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"{}/>",
+                from.x, from.y, to.x, to.y, svg::stroke_attributes(&self.properties.style))
+        });
+        let direction = self.get_direction();
+        let elements: Vec<String> = shaft.into_iter()
+            .chain(self.properties.style.start_marker.to_svg(self.from, -direction, &self.properties.style))
+            .chain(self.properties.style.end_marker.to_svg(self.to, direction, &self.properties.style))
+            .collect();
+        let line = match elements.as_slice() {
+            [line] => line.clone(),
+            _ => format!("<g>{}</g>", elements.concat()),
+        };
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(line, (self.from + self.to) / 2.0, &self.properties.style))
     }
 }
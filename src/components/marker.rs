@@ -0,0 +1,120 @@
+use std::f32::consts::PI;
+
+use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{add_triangle, stroke, style::{Color, Style}};
+
+const MIN_SIZE: f32 = 6.0;
+const SIZE_PER_STROKE_WIDTH: f32 = 3.0;
+const DISC_SEGMENTS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Marker {
+    None,
+    OpenArrow,
+    FilledArrow,
+    Diamond,
+    Circle,
+    Bar,
+}
+
+enum Shape {
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+    Disc { center: Vec2, radius: f32 },
+}
+
+fn get_size(stroke_width: f32) -> f32 {
+    MIN_SIZE + SIZE_PER_STROKE_WIDTH * stroke_width
+}
+
+fn cross(v1: Vec2, v2: Vec2) -> f32 {
+    v1.x * v2.y - v1.y * v2.x
+}
+
+impl Marker {
+    // `direction` points out of the line, so the marker's tip sits exactly on `tip`
+    fn get_shape(self, tip: Vec2, direction: Vec2, stroke_width: f32) -> Option<Shape> {
+        let size = get_size(stroke_width);
+        let back = -direction * size;
+        let side = vec2(-direction.y, direction.x) * size / 2.0;
+        match self {
+            Marker::None => None,
+            Marker::OpenArrow => Some(Shape::Polyline(vec![tip + back + side, tip, tip + back - side])),
+            Marker::FilledArrow => Some(Shape::Polygon(vec![tip, tip + back + side, tip + back - side])),
+            Marker::Diamond => Some(Shape::Polygon(vec![tip, tip + back / 2.0 + side * 2.0 / 3.0, tip + back, tip + back / 2.0 - side * 2.0 / 3.0])),
+            Marker::Circle => Some(Shape::Disc { center: tip + back / 2.0, radius: size / 2.0 }),
+            Marker::Bar => Some(Shape::Polyline(vec![tip + side, tip - side])),
+        }
+    }
+
+    // How far the line has to stop short of its endpoint so it doesn't poke through a solid marker
+    pub fn get_inset(self, stroke_width: f32) -> f32 {
+        match self {
+            Marker::FilledArrow | Marker::Diamond | Marker::Circle => get_size(stroke_width),
+            Marker::None | Marker::OpenArrow | Marker::Bar => 0.0,
+        }
+    }
+
+    pub fn add_vertices(self, vertices: &mut Vec<f32>, tip: Vec2, direction: Vec2, style: &Style, color: Color) {
+        match self.get_shape(tip, direction, style.stroke_width) {
+            None => (),
+            Some(Shape::Polygon(points)) => {
+                for i in 1..points.len() - 1 {
+                    add_triangle(vertices, points[0], points[i], points[i + 1], color);
+                }
+            }
+            Some(Shape::Polyline(points)) => stroke::add_stroke(vertices, &points, false, style.stroke_width, style.join, color),
+            Some(Shape::Disc { center, radius }) => {
+                let point = |i: usize| {
+                    let angle = i as f32 / DISC_SEGMENTS as f32 * 2.0 * PI;
+                    center + vec2(angle.cos(), angle.sin()) * radius
+                };
+                for i in 0..DISC_SEGMENTS {
+                    add_triangle(vertices, center, point(i), point(i + 1), color);
+                }
+            }
+        }
+    }
+
+    // The marker as discs for `Transform::get_bounds`
+    pub fn get_extent(self, tip: Vec2, direction: Vec2, style: &Style) -> Vec<(Vec2, f32)> {
+        match self.get_shape(tip, direction, style.stroke_width) {
+            None => vec![],
+            Some(Shape::Polygon(points)) => points.into_iter().map(|point| (point, 0.0)).collect(),
+            Some(Shape::Polyline(points)) => stroke::get_extent(&points, false, style.stroke_width, style.join),
+            Some(Shape::Disc { center, radius }) => vec![(center, radius)],
+        }
+    }
+
+    pub fn contains(self, tip: Vec2, direction: Vec2, stroke_width: f32, point: Vec2) -> bool {
+        match self.get_shape(tip, direction, stroke_width) {
+            None => false,
+            Some(Shape::Polygon(points)) => {
+                let sides: Vec<f32> = (0..points.len())
+                    .map(|i| cross(points[(i + 1) % points.len()] - points[i], point - points[i]))
+                    .collect();
+                sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+            }
+            Some(Shape::Polyline(points)) => stroke::stroke_contains(&points, false, stroke_width, point),
+            Some(Shape::Disc { center, radius }) => (point - center).magnitude() <= radius,
+        }
+    }
+
+    pub fn to_svg(self, tip: Vec2, direction: Vec2, style: &Style) -> Option<String> {
+        let color = style.get_stroke(false)?;
+        match self.get_shape(tip, direction, style.stroke_width)? {
+            Shape::Polygon(points) => Some(format!("<polygon points=\"{}\"{} stroke=\"none\"/>",
+                svg::points(&points), svg::fill_attributes(color))),
+            Shape::Polyline(points) => Some(format!("<polyline points=\"{}\" fill=\"none\"{}/>",
+                svg::points(&points), svg::stroke_attributes(style))),
+            Shape::Disc { center, radius } => {
+                let center = svg::point(center);
+                Some(format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\"{} stroke=\"none\"/>", center.x, center.y, radius, svg::fill_attributes(color)))
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{Component, ComponentPreview, SavedComponent, add_triangle, mouse_in_triangle, get_path_point, find_path_position, line::Line, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Polygon {
+    #[serde(flatten)]
+    properties: Properties,
+    points: Vec<Vec2>,
+}
+
+fn cross(v1: Vec2, v2: Vec2) -> f32 {
+    v1.x * v2.y - v1.y * v2.x
+}
+
+// Positive when the points wind anticlockwise
+fn get_signed_area(points: &[Vec2]) -> f32 {
+    (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum::<f32>() / 2.0
+}
+
+// Ear clipping, so concave polygons fill correctly. Self-intersecting polygons have no ears left at some
+// point; dropping a vertex there keeps the loop finite at the cost of some missing fill
+fn triangulate(points: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut remaining: Vec<Vec2> = vec![];
+    for point in points {
+        if remaining.last() != Some(point) {
+            remaining.push(*point);
+        }
+    }
+    while remaining.len() > 1 && remaining.first() == remaining.last() {
+        remaining.pop();
+    }
+    if get_signed_area(&remaining) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = vec![];
+    while remaining.len() >= 3 {
+        let count = remaining.len();
+        let corner = |i: usize| (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+        let is_ear = |i: usize| {
+            let (previous, point, next) = corner(i);
+            cross(point - previous, next - point) > 0.0 && !remaining.iter()
+                .filter(|other| ![previous, point, next].contains(other))
+                .any(|other| mouse_in_triangle(*other, previous, point, next))
+        };
+
+        let ear = (0..count).find(|i| is_ear(*i)).unwrap_or(0);
+        let (previous, point, next) = corner(ear);
+        if cross(point - previous, next - point) > 0.0 {
+            triangles.push([previous, point, next]);
+        }
+        remaining.remove(ear);
+    }
+    triangles
+}
+
+// Even-odd crossing test, so it agrees with the triangulation for simple polygons
+fn polygon_contains(points: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for i in 0..points.len() {
+        let (from, to) = (points[i], points[(i + 1) % points.len()]);
+        if (from.y > point.y) != (to.y > point.y)
+            && point.x < from.x + (point.y - from.y) / (to.y - from.y) * (to.x - from.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { points: vertices, properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    // Area centroid, falling back to the vertex average for degenerate polygons
+    fn get_centroid(&self) -> Vec2 {
+        let area = get_signed_area(&self.points);
+        if area.abs() < f32::EPSILON {
+            return self.points.iter().sum::<Vec2>() / self.points.len() as f32;
+        }
+
+        let sum: Vec2 = (0..self.points.len()).map(|i| {
+            let (from, to) = (self.points[i], self.points[(i + 1) % self.points.len()]);
+            (from + to) * cross(from, to)
+        }).sum();
+        sum / (6.0 * area)
+    }
+}
+
+impl ComponentPreview for Polygon {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            0 | 1 => vec![],
+            2 => vec![Box::new(Line::new(vertices, style))],
+            _ => vec![Box::new(Polygon::new(vertices, style))],
+        }
+    }
+}
+
+impl Component for Polygon {
+    fn get_vertices(&self, _tolerance: f32) -> Vec<f32> {
+        let mut vertices = vec![];
+        if let Some(fill) = self.properties.style.get_fill(self.properties.highlighted) {
+            for [v1, v2, v3] in triangulate(&self.points) {
+                add_triangle(&mut vertices, v1, v2, v3, fill);
+            }
+        }
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            stroke::add_stroke(&mut vertices, &self.points, true, self.properties.style.stroke_width, self.properties.style.join, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, self.get_centroid(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        (self.properties.style.filled && polygon_contains(&self.points, mouse_pos))
+            || stroke::stroke_contains(&self.points, true, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.label.contains(self.get_centroid(), mouse_pos)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.points.clone())
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match self.points.get_mut(handle) {
+            Some(point) => *point = position,
+            None => panic!("Polygon has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.get_handles()
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_handles(), true, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_handles(), true, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Polygon(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Polygon(polygon) = saved else {
+            panic!("Polygon can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = polygon.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let polygon = format!("<polygon points=\"{}\"{}/>", svg::points(&self.points), svg::style_attributes(&self.properties.style));
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(polygon, self.get_centroid(), &self.properties.style))
+    }
+}
@@ -0,0 +1,140 @@
+use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{Component, ComponentPreview, SavedComponent, get_path_point, find_path_position, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Polyline {
+    #[serde(flatten)]
+    properties: Properties,
+    points: Vec<Vec2>,
+}
+
+impl Polyline {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { points: vertices, properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_reversed(&self) -> Vec<Vec2> {
+        self.points.iter().rev().copied().collect()
+    }
+
+    // Marker directions point out of the path at each end
+    fn get_directions(&self) -> (Vec2, Vec2) {
+        (-stroke::get_start_direction(&self.points), -stroke::get_start_direction(&self.get_reversed()))
+    }
+
+    fn get_midpoint(&self) -> Vec2 {
+        stroke::trim_start(self.points.clone(), stroke::get_length(&self.points) / 2.0)[0]
+    }
+
+    // The visible part of the path between the markers, if they leave any
+    fn get_shaft(&self) -> Option<Vec<Vec2>> {
+        let start_inset = self.properties.style.start_marker.get_inset(self.properties.style.stroke_width);
+        let end_inset = self.properties.style.end_marker.get_inset(self.properties.style.stroke_width);
+        if start_inset + end_inset >= stroke::get_length(&self.points) && start_inset + end_inset > 0.0 {
+            return None;
+        }
+
+        let mut points = stroke::trim_start(self.get_reversed(), end_inset);
+        points.reverse();
+        Some(stroke::trim_start(points, start_inset))
+    }
+}
+
+impl ComponentPreview for Polyline {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            0 | 1 => vec![],
+            _ => vec![Box::new(Polyline::new(vertices, style))],
+        }
+    }
+}
+
+impl Component for Polyline {
+    fn get_vertices(&self, _tolerance: f32) -> Vec<f32> {
+        let mut vertices = vec![];
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            if let Some(points) = self.get_shaft() {
+                stroke::add_stroke(&mut vertices, &points, false, self.properties.style.stroke_width, self.properties.style.join, stroke);
+            }
+            let (start_direction, end_direction) = self.get_directions();
+            self.properties.style.start_marker.add_vertices(&mut vertices, self.points[0], start_direction, &self.properties.style, stroke);
+            self.properties.style.end_marker.add_vertices(&mut vertices, self.points[self.points.len() - 1], end_direction, &self.properties.style, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, self.get_midpoint(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let (start_direction, end_direction) = self.get_directions();
+        stroke::stroke_contains(&self.points, false, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.style.start_marker.contains(self.points[0], start_direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.style.end_marker.contains(self.points[self.points.len() - 1], end_direction, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.label.contains(self.get_midpoint(), mouse_pos)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(self.points.clone())
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match self.points.get_mut(handle) {
+            Some(point) => *point = position,
+            None => panic!("Polyline has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.get_handles()
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_handles(), false, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_handles(), false, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Polyline(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Polyline(polyline) = saved else {
+            panic!("Polyline can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = polyline.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let shaft = self.get_shaft().map(|points| {
+            format!("<polyline points=\"{}\" fill=\"none\"{}/>", svg::points(&points), svg::stroke_attributes(&self.properties.style))
+        });
+        let (start_direction, end_direction) = self.get_directions();
+        let elements: Vec<String> = shaft.into_iter()
+            .chain(self.properties.style.start_marker.to_svg(self.points[0], start_direction, &self.properties.style))
+            .chain(self.properties.style.end_marker.to_svg(self.points[self.points.len() - 1], end_direction, &self.properties.style))
+            .collect();
+        let polyline = match elements.as_slice() {
+            [polyline] => polyline.clone(),
+            _ => format!("<g>{}</g>", elements.concat()),
+        };
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(polyline, self.get_midpoint(), &self.properties.style))
+    }
+}
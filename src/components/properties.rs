@@ -0,0 +1,22 @@
+use serde::{Serialize, Deserialize};
+
+use super::{ComponentId, LayerId, style::Style, text::Label, transform::Transform};
+
+// What every component has besides its shape. Flattened into the component when saved, so documents keep the
+// same fields
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Properties {
+    pub id: ComponentId,
+    pub layer: LayerId,
+    pub style: Style,
+    pub label: Label,
+    pub transform: Transform,
+    #[serde(skip)]
+    pub highlighted: bool,
+}
+
+impl Properties {
+    pub fn new(style: Style, label: Label, transform: Transform) -> Self {
+        Self { id: 0, layer: 0, style, label, transform, highlighted: false }
+    }
+}
@@ -1,52 +1,182 @@
+use std::f32::consts::FRAC_PI_2;
+
 use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
 
-use crate::state::State;
+use crate::svg;
 
-use super::{Component, ComponentPreview, mouse_in_triangle};
+use super::{Component, ComponentPreview, SavedComponent, add_triangle, get_segment_count, get_path_point, find_path_position, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Rect {
+    #[serde(flatten)]
+    properties: Properties,
     from: Vec2,
     to: Vec2,
 }
 
 impl Rect {
-    pub fn new(vertices: Vec<Vec2>, state: &State) -> Self {
-        let from = state.world_space_to_screen_space(vertices[0]);
-        let to = state.world_space_to_screen_space(vertices[1]);
-        Self { from, to }
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { from: vertices[0], to: vertices[1], properties: Properties::new(style, Label::new(""), Transform::default()) }
+    }
+
+    fn get_center(&self) -> Vec2 {
+        (self.from + self.to) / 2.0
+    }
+
+    fn get_half_size(&self) -> Vec2 {
+        nalgebra_glm::abs(&(self.to - self.from)) / 2.0
+    }
+
+    // Limited so opposite corners at most meet in the middle of the shorter side
+    fn get_corner_radius(&self) -> f32 {
+        let half_size = self.get_half_size();
+        self.properties.style.corner_radius.clamp(0.0, half_size.x.min(half_size.y))
+    }
+
+    // The centres of the corner arcs, anticlockwise from the bottom right
+    fn get_inset_corners(&self) -> [Vec2; 4] {
+        let (center, radius) = (self.get_center(), self.get_corner_radius());
+        let inset = self.get_half_size() - vec2(radius, radius);
+        [vec2(inset.x, -inset.y), inset, vec2(-inset.x, inset.y), -inset].map(|corner| center + corner)
+    }
+
+    // Anticlockwise from the bottom right corner, each corner an arc about a point inset by the radius
+    fn get_perimeter(&self, tolerance: f32) -> Vec<Vec2> {
+        let (corners, radius) = (self.get_inset_corners(), self.get_corner_radius());
+        if radius == 0.0 {
+            return corners.to_vec();
+        }
+
+        let steps = get_segment_count(radius, FRAC_PI_2, tolerance);
+        corners.iter().enumerate().flat_map(|(i, corner)| (0..=steps).map(move |step| {
+            let angle = (i as f32 - 1.0 + step as f32 / steps as f32) * FRAC_PI_2;
+            corner + vec2(angle.cos(), angle.sin()) * radius
+        })).collect()
+    }
+
+    // Signed distance to the outline, negative inside
+    fn get_distance(&self, point: Vec2) -> f32 {
+        let radius = self.get_corner_radius();
+        let offset = nalgebra_glm::abs(&(point - self.get_center())) - self.get_half_size() + vec2(radius, radius);
+        offset.map(|value| value.max(0.0)).magnitude() + offset.x.max(offset.y).min(0.0) - radius
     }
 }
 
 impl ComponentPreview for Rect {
-    fn get_preview(vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>> {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
         match vertices.len() {
-            2 => vec![Box::new(Rect::new(vertices, state))],
+            2 => vec![Box::new(Rect::new(vertices, style))],
             _ => vec![],
         }
     }
 }
 
 impl Component for Rect {
-    fn get_vertices(&self) -> Vec<f32> {
-        let v1 = vec2(self.from.x, self.from.y);
-        let v2 = vec2(self.from.x, self.to.y);
-        let v3 = vec2(self.to.x, self.from.y);
-        let v4 = vec2(self.to.x, self.to.y);
-        vec![
-            v1.x, v1.y,
-            v2.x, v2.y,
-            v3.x, v3.y,
-            v2.x, v2.y,
-            v3.x, v3.y,
-            v4.x, v4.y,
-        ]
+    fn get_vertices(&self, tolerance: f32) -> Vec<f32> {
+        let tolerance = self.properties.transform.get_local_tolerance(tolerance);
+        let mut vertices = vec![];
+        let perimeter = self.get_perimeter(tolerance);
+        if let Some(fill) = self.properties.style.get_fill(self.properties.highlighted) {
+            for i in 1..perimeter.len() - 1 {
+                add_triangle(&mut vertices, perimeter[0], perimeter[i], perimeter[i + 1], fill);
+            }
+        }
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            stroke::add_stroke(&mut vertices, &perimeter, true, self.properties.style.stroke_width, self.properties.style.join, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, self.get_center(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
     }
 
     fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
-        let v1 = vec2(self.from.x, self.from.y);
-        let v2 = vec2(self.from.x, self.to.y);
-        let v3 = vec2(self.to.x, self.from.y);
-        let v4 = vec2(self.to.x, self.to.y);
-        mouse_in_triangle(mouse_pos, v1, v2, v3) && mouse_in_triangle(mouse_pos, v2, v3, v4)
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let distance = self.get_distance(mouse_pos);
+        (self.properties.style.filled && distance <= 0.0)
+            || distance.abs() <= self.properties.style.stroke_width / 2.0
+            || self.properties.label.contains(self.get_center(), mouse_pos)
+    }
+
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        let mut extent = self.properties.label.get_extent(self.get_center());
+        if self.properties.style.is_drawn() {
+            let (corners, radius) = (self.get_inset_corners(), self.get_corner_radius());
+            if radius == 0.0 {
+                extent.extend(stroke::get_extent(&corners, true, self.properties.style.stroke_width, self.properties.style.join));
+            } else {
+                extent.extend(corners.map(|corner| (corner, radius + self.properties.style.stroke_width / 2.0)));
+            }
+        }
+        self.properties.transform.get_bounds(&extent)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
     }
-}
\ No newline at end of file
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![
+            vec2(self.from.x, self.from.y),
+            vec2(self.from.x, self.to.y),
+            vec2(self.to.x, self.to.y),
+            vec2(self.to.x, self.from.y),
+        ])
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.from = position,
+            1 => (self.from.x, self.to.y) = (position.x, position.y),
+            2 => self.to = position,
+            3 => (self.to.x, self.from.y) = (position.x, position.y),
+            _ => panic!("Rect has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        let corners = self.get_handles();
+        let midpoints = (0..4).map(|i| (corners[i] + corners[(i + 1) % 4]) / 2.0);
+        let mut points: Vec<_> = corners.iter().copied().chain(midpoints).collect();
+        points.push(self.properties.transform.apply(self.get_center()));
+        points
+    }
+
+    // Around the corners, ignoring any rounding
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_handles(), true, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_handles(), true, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Rect(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Rect(rect) = saved else {
+            panic!("Rect can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = rect.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let from = svg::point(self.from);
+        let to = svg::point(self.to);
+        let min = nalgebra_glm::min2(&from, &to);
+        let size = nalgebra_glm::abs(&(to - from));
+        let radius = self.get_corner_radius();
+        let corners = if radius > 0.0 { format!(" rx=\"{}\"", radius) } else { String::new() };
+        let rect = format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}{}/>",
+            min.x, min.y, size.x, size.y, corners, svg::style_attributes(&self.properties.style));
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(rect, self.get_center(), &self.properties.style))
+    }
+}
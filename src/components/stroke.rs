@@ -0,0 +1,192 @@
+use nalgebra_glm::{Vec2, vec2};
+
+use super::{add_triangle, style::{Color, Join}};
+
+// Same default as SVG, so exported strokes fall back to bevels at the same angles
+pub const MITER_LIMIT: f32 = 4.0;
+const ROUND_JOIN_SEGMENT_LENGTH: f32 = 2.0;
+
+fn perpendicular(direction: Vec2) -> Vec2 {
+    vec2(-direction.y, direction.x)
+}
+
+fn cross(v1: Vec2, v2: Vec2) -> f32 {
+    v1.x * v2.y - v1.y * v2.x
+}
+
+// Outer offset points for each side of a segment: [from_left, from_right, to_left, to_right]
+fn get_offsets(from: Vec2, to: Vec2, half_width: f32) -> [Vec2; 4] {
+    let offset = perpendicular((to - from).normalize()) * half_width;
+    [from + offset, from - offset, to + offset, to - offset]
+}
+
+// None past the miter limit, where the join falls back to a bevel
+fn get_miter(point: Vec2, outer_from: Vec2, outer_to: Vec2) -> Option<Vec2> {
+    let half_width = outer_from.magnitude();
+    let bisector = (outer_from + outer_to).normalize();
+    let miter_length = half_width / bisector.dot(&outer_from.normalize());
+    (miter_length / half_width <= MITER_LIMIT).then_some(point + bisector * miter_length)
+}
+
+fn add_join(vertices: &mut Vec<f32>, point: Vec2, base: Vec2, outer_from: Vec2, outer_to: Vec2, join: Join, color: Color) {
+    let half_width = outer_from.magnitude();
+    match join {
+        Join::Bevel => add_triangle(vertices, base, point + outer_from, point + outer_to, color),
+        Join::Miter => match get_miter(point, outer_from, outer_to) {
+            Some(miter) => {
+                add_triangle(vertices, base, point + outer_from, miter, color);
+                add_triangle(vertices, base, miter, point + outer_to, color);
+            }
+            None => add_join(vertices, point, base, outer_from, outer_to, Join::Bevel, color),
+        },
+        Join::Round => {
+            if base != point {
+                add_triangle(vertices, base, point + outer_from, point, color);
+                add_triangle(vertices, base, point, point + outer_to, color);
+            }
+            let angle = outer_from.angle(&outer_to);
+            let direction = cross(outer_from, outer_to).signum();
+            let steps = ((angle * half_width / ROUND_JOIN_SEGMENT_LENGTH).ceil() as usize).max(1);
+            let mut previous = outer_from;
+            for i in 1..=steps {
+                let rotation = direction * angle * i as f32 / steps as f32;
+                let next = vec2(
+                    outer_from.x * rotation.cos() - outer_from.y * rotation.sin(),
+                    outer_from.x * rotation.sin() + outer_from.y * rotation.cos());
+                add_triangle(vertices, point, point + previous, point + next, color);
+                previous = next;
+            }
+        }
+    }
+}
+
+fn get_segments(points: &[Vec2], closed: bool) -> Vec<(Vec2, Vec2)> {
+    let segment_count = if closed { points.len() } else { points.len().saturating_sub(1) };
+    (0..segment_count)
+        .map(|i| (points[i], points[(i + 1) % points.len()]))
+        .filter(|(from, to)| from != to)
+        .collect()
+}
+
+// Segments meet at the intersection of their inner edges, so translucent strokes are never covered twice
+pub fn add_stroke(vertices: &mut Vec<f32>, points: &[Vec2], closed: bool, width: f32, join: Join, color: Color) {
+    let half_width = width / 2.0;
+    let segments = get_segments(points, closed);
+    let mut offsets: Vec<[Vec2; 4]> = segments.iter()
+        .map(|(from, to)| get_offsets(*from, *to, half_width))
+        .collect();
+
+    let mut joins = vec![];
+    let join_count = if closed { segments.len() } else { segments.len().saturating_sub(1) };
+    for i in 0..join_count {
+        let next = (i + 1) % segments.len();
+        let (from, point) = segments[i];
+        let (_, to) = segments[next];
+        let incoming = (point - from).normalize();
+        let outgoing = (to - point).normalize();
+        let turn = cross(incoming, outgoing);
+        if turn.abs() < f32::EPSILON {
+            continue;
+        }
+
+        // The gap to fill is on the outside of the turn
+        let outer_from = -perpendicular(incoming) * turn.signum() * half_width;
+        let outer_to = -perpendicular(outgoing) * turn.signum() * half_width;
+        let bisector = (outer_from + outer_to).normalize();
+        let inner = point - bisector * half_width / bisector.dot(&outer_from.normalize());
+        let trim = (point - inner).dot(&incoming).abs();
+        let base = if trim <= (point - from).magnitude() && trim <= (to - point).magnitude() {
+            let side = if turn > 0.0 { 0 } else { 1 };
+            offsets[i][2 + side] = inner;
+            offsets[next][side] = inner;
+            inner
+        } else {
+            point
+        };
+        joins.push((point, base, outer_from, outer_to));
+    }
+
+    for [from_left, from_right, to_left, to_right] in offsets {
+        add_triangle(vertices, from_left, from_right, to_left, color);
+        add_triangle(vertices, from_right, to_left, to_right, color);
+    }
+    for (point, base, outer_from, outer_to) in joins {
+        add_join(vertices, point, base, outer_from, outer_to, join, color);
+    }
+}
+
+// The outline of a stroke as discs for `Transform::get_bounds`: the corners of every segment, plus the
+// miter tips or round joins that can reach past them. Bevels never do
+pub fn get_extent(points: &[Vec2], closed: bool, width: f32, join: Join) -> Vec<(Vec2, f32)> {
+    let half_width = width / 2.0;
+    let segments = get_segments(points, closed);
+    let mut extent: Vec<(Vec2, f32)> = segments.iter()
+        .flat_map(|(from, to)| get_offsets(*from, *to, half_width))
+        .map(|point| (point, 0.0))
+        .collect();
+
+    let join_count = if closed { segments.len() } else { segments.len().saturating_sub(1) };
+    for i in 0..join_count {
+        let (from, point) = segments[i];
+        let (_, to) = segments[(i + 1) % segments.len()];
+        let incoming = (point - from).normalize();
+        let outgoing = (to - point).normalize();
+        let turn = cross(incoming, outgoing);
+        if turn.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let outer_from = -perpendicular(incoming) * turn.signum() * half_width;
+        let outer_to = -perpendicular(outgoing) * turn.signum() * half_width;
+        match join {
+            Join::Bevel => {}
+            Join::Miter => extent.extend(get_miter(point, outer_from, outer_to).map(|miter| (miter, 0.0))),
+            Join::Round => extent.push((point, half_width)),
+        }
+    }
+    extent
+}
+
+pub fn distance_to_segment(point: Vec2, from: Vec2, to: Vec2) -> f32 {
+    let direction = to - from;
+    let length_squared = direction.magnitude_squared();
+    if length_squared == 0.0 {
+        return (point - from).magnitude();
+    }
+
+    let t = ((point - from).dot(&direction) / length_squared).clamp(0.0, 1.0);
+    (point - (from + direction * t)).magnitude()
+}
+
+pub fn stroke_contains(points: &[Vec2], closed: bool, width: f32, point: Vec2) -> bool {
+    get_segments(points, closed)
+        .iter()
+        .any(|(from, to)| distance_to_segment(point, *from, *to) <= width / 2.0)
+}
+
+pub fn get_length(points: &[Vec2]) -> f32 {
+    points.windows(2).map(|segment| (segment[1] - segment[0]).magnitude()).sum()
+}
+
+// Removes `length` from the start of the path, measured along it
+pub fn trim_start(mut points: Vec<Vec2>, mut length: f32) -> Vec<Vec2> {
+    while points.len() >= 2 {
+        let direction = points[1] - points[0];
+        let segment = direction.magnitude();
+        if segment > length {
+            points[0] += direction / segment * length;
+            break;
+        }
+        length -= segment;
+        points.remove(0);
+    }
+    points
+}
+
+// Direction of travel out of the path's first point, skipping repeated points
+pub fn get_start_direction(points: &[Vec2]) -> Vec2 {
+    points.windows(2)
+        .find(|segment| segment[0] != segment[1])
+        .map(|segment| (segment[1] - segment[0]).normalize())
+        .unwrap_or(vec2(1.0, 0.0))
+}
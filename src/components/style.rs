@@ -0,0 +1,84 @@
+use serde::{Serialize, Deserialize};
+
+use super::marker::Marker;
+
+pub type Color = [f32; 4];
+
+const HIGHLIGHT_AMOUNT: f32 = 0.4;
+
+pub fn highlight(color: Color) -> Color {
+    [
+        color[0] + (1.0 - color[0]) * HIGHLIGHT_AMOUNT,
+        color[1] + (1.0 - color[1]) * HIGHLIGHT_AMOUNT,
+        color[2] + (1.0 - color[2]) * HIGHLIGHT_AMOUNT,
+        color[3],
+    ]
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Join {
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Style {
+    pub fill: Color,
+    pub filled: bool,
+    pub stroke: Color,
+    pub stroke_width: f32,
+    pub join: Join,
+    pub start_marker: Marker,
+    pub end_marker: Marker,
+    pub text: Color,
+    // Only rects have corners to round
+    pub corner_radius: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: [1.0, 0.0, 0.0, 1.0],
+            filled: true,
+            stroke: [1.0, 1.0, 1.0, 1.0],
+            stroke_width: 2.0,
+            join: Join::Miter,
+            start_marker: Marker::None,
+            end_marker: Marker::None,
+            text: [1.0, 1.0, 1.0, 1.0],
+            corner_radius: 0.0,
+        }
+    }
+}
+
+impl Style {
+    // Whether a shape in this style draws anything besides its label
+    pub fn is_drawn(&self) -> bool {
+        self.filled || self.stroke_width > 0.0
+    }
+
+    pub fn get_fill(&self, highlighted: bool) -> Option<Color> {
+        match (self.filled, highlighted) {
+            (false, _) => None,
+            (true, false) => Some(self.fill),
+            (true, true) => Some(highlight(self.fill)),
+        }
+    }
+
+    pub fn get_text(&self, highlighted: bool) -> Color {
+        if highlighted {
+            highlight(self.text)
+        } else {
+            self.text
+        }
+    }
+
+    pub fn get_stroke(&self, highlighted: bool) -> Option<Color> {
+        match (self.stroke_width > 0.0, highlighted) {
+            (false, _) => None,
+            (true, false) => Some(self.stroke),
+            (true, true) => Some(highlight(self.stroke)),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use nalgebra_glm::{Vec2, vec2};
+use serde::{Serialize, Deserialize};
+
+use crate::svg;
+
+use super::{Component, ComponentPreview, SavedComponent, add_triangle, get_path_point, find_path_position, font, style::{Color, Style}, transform::Transform, properties::Properties};
+
+const DEFAULT_TEXT: &str = "Text";
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+struct LayoutLine {
+    glyphs: Vec<(ttf_parser::GlyphId, f32)>,
+    origin: Vec2,
+}
+
+// Text laid out in world space as a block centred on an anchor point, with lines aligned within the block
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub text: String,
+    pub font_size: f32,
+    pub alignment: Alignment,
+}
+
+impl Label {
+    pub fn new(text: &str) -> Self {
+        Self { text: text.to_string(), font_size: DEFAULT_FONT_SIZE, alignment: Alignment::Center }
+    }
+
+    fn get_size(&self) -> Vec2 {
+        let width = self.text.lines()
+            .map(|line| font::shape_line(line).1)
+            .fold(0.0, f32::max);
+        vec2(width, self.text.lines().count() as f32 * font::get_line_height()) * self.font_size
+    }
+
+    fn layout(&self, anchor: Vec2) -> Vec<LayoutLine> {
+        let size = self.get_size();
+        let top = anchor.y + size.y / 2.0;
+        self.text.lines().enumerate().map(|(i, line)| {
+            let (glyphs, advance) = font::shape_line(line);
+            let width = advance * self.font_size;
+            let x = match self.alignment {
+                Alignment::Left => anchor.x - size.x / 2.0,
+                Alignment::Center => anchor.x - width / 2.0,
+                Alignment::Right => anchor.x + size.x / 2.0 - width,
+            };
+            let y = top - (font::get_ascender() + i as f32 * font::get_line_height()) * self.font_size;
+            LayoutLine { glyphs, origin: vec2(x, y) }
+        }).collect()
+    }
+
+    pub fn get_bounds(&self, anchor: Vec2) -> Option<(Vec2, Vec2)> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let size = self.get_size();
+        Some((anchor - size / 2.0, anchor + size / 2.0))
+    }
+
+    // The corners of the label's box, for `Transform::get_bounds`
+    pub fn get_extent(&self, anchor: Vec2) -> Vec<(Vec2, f32)> {
+        self.get_bounds(anchor).map_or(vec![], |(min, max)| {
+            [min, vec2(min.x, max.y), max, vec2(max.x, min.y)].map(|corner| (corner, 0.0)).to_vec()
+        })
+    }
+
+    pub fn add_vertices(&self, vertices: &mut Vec<f32>, anchor: Vec2, color: Color) {
+        for line in self.layout(anchor) {
+            for (glyph, offset) in line.glyphs {
+                let origin = line.origin + vec2(offset * self.font_size, 0.0);
+                for triangle in font::get_glyph_triangles(glyph).chunks_exact(3) {
+                    let [v1, v2, v3] = [0, 1, 2].map(|i| origin + triangle[i] * self.font_size);
+                    add_triangle(vertices, v1, v2, v3, color);
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, anchor: Vec2, point: Vec2) -> bool {
+        self.get_bounds(anchor)
+            .is_some_and(|(min, max)| (min.x..=max.x).contains(&point.x) && (min.y..=max.y).contains(&point.y))
+    }
+
+    pub fn to_svg(&self, anchor: Vec2, style: &Style) -> Option<String> {
+        let (min, max) = self.get_bounds(anchor)?;
+        let (text_anchor, x) = match self.alignment {
+            Alignment::Left => ("start", min.x),
+            Alignment::Center => ("middle", anchor.x),
+            Alignment::Right => ("end", max.x),
+        };
+        let lines: String = self.layout(anchor).iter().zip(self.text.lines()).map(|(line, text)| {
+            let position = svg::point(vec2(x, line.origin.y));
+            format!("<tspan x=\"{}\" y=\"{}\">{}</tspan>", position.x, position.y, svg::escape(text))
+        }).collect();
+        Some(format!("<text font-family=\"Inter\" font-size=\"{}\" text-anchor=\"{}\"{}>{}</text>",
+            self.font_size, text_anchor, svg::fill_attributes(style.text), lines))
+    }
+
+    // Wraps a shape's SVG element in a group with its label, if it has one
+    pub fn with_svg(&self, element: String, anchor: Vec2, style: &Style) -> String {
+        match self.to_svg(anchor, style) {
+            Some(label) => format!("<g>{}{}</g>", element, label),
+            None => element,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Text {
+    #[serde(flatten)]
+    properties: Properties,
+    position: Vec2,
+}
+
+impl Text {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
+        Self { properties: Properties::new(style, Label::new(DEFAULT_TEXT), Transform::default()), position: vertices[0] }
+    }
+
+    // The corners of the label's box, in the same order as a rect's
+    fn get_outline(&self) -> Option<Vec<Vec2>> {
+        let (min, max) = self.properties.label.get_bounds(self.position)?;
+        Some(self.properties.transform.apply_all(vec![min, vec2(min.x, max.y), max, vec2(max.x, min.y)]))
+    }
+}
+
+impl ComponentPreview for Text {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
+        match vertices.len() {
+            1 => vec![Box::new(Text::new(vertices, style))],
+            _ => vec![],
+        }
+    }
+}
+
+impl Component for Text {
+    fn get_vertices(&self, _tolerance: f32) -> Vec<f32> {
+        let mut vertices = vec![];
+        self.properties.label.add_vertices(&mut vertices, self.position, self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
+    }
+
+    // The whole text box, which is also what hit-testing uses, rather than just the glyphs
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        self.properties.transform.get_bounds(&self.properties.label.get_extent(self.position))
+    }
+
+    fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        self.properties.label.contains(self.position, mouse_pos)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        vec![self.properties.transform.apply(self.position)]
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.position = position,
+            _ => panic!("Text has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.get_handles()
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_outline()?, true, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_outline()?, true, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Text(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Text(text) = saved else {
+            panic!("Text can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = text.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        self.properties.label.to_svg(self.position, &self.properties.style)
+            .map(|text| self.properties.transform.wrap_svg(text))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,122 @@
+use nalgebra_glm::{Mat2, Vec2, vec2};
+use serde::{Serialize, Deserialize};
+
+use super::{VERTEX_SIZE, merge_bounds};
+
+// Maps a component's own coordinates to world space: scale, then skew, then rotate about the local origin, then translate
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: Vec2,
+    // Radians anticlockwise
+    pub rotation: f32,
+    pub scale: Vec2,
+    // Radians, shearing x along y
+    pub skew: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self { translation: vec2(0.0, 0.0), rotation: 0.0, scale: vec2(1.0, 1.0), skew: 0.0 }
+    }
+}
+
+impl Transform {
+    // Any invertible matrix factors into a rotation times an upper triangular matrix, which is a skew times a scale
+    fn from_matrix(linear: Mat2, translation: Vec2) -> Self {
+        let rotation = linear[(1, 0)].atan2(linear[(0, 0)]);
+        let (sin, cos) = rotation.sin_cos();
+        let scale = vec2(linear[(0, 0)].hypot(linear[(1, 0)]), cos * linear[(1, 1)] - sin * linear[(0, 1)]);
+        let skew = ((cos * linear[(0, 1)] + sin * linear[(1, 1)]) / scale.y).atan();
+        Self { translation, rotation, scale, skew }
+    }
+
+    fn get_linear(&self) -> Mat2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let rotation = Mat2::new(cos, -sin, sin, cos);
+        let skew = Mat2::new(1.0, self.skew.tan(), 0.0, 1.0);
+        rotation * skew * Mat2::new(self.scale.x, 0.0, 0.0, self.scale.y)
+    }
+
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        self.get_linear() * point + self.translation
+    }
+
+    // A collapsed transform has no inverse, and nothing can be under the mouse then anyway
+    pub fn invert(&self, point: Vec2) -> Vec2 {
+        match self.get_linear().try_inverse() {
+            Some(inverse) => inverse * (point - self.translation),
+            None => vec2(f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    pub fn apply_all(&self, points: Vec<Vec2>) -> Vec<Vec2> {
+        points.into_iter().map(|point| self.apply(point)).collect()
+    }
+
+    pub fn apply_vertices(&self, mut vertices: Vec<f32>) -> Vec<f32> {
+        for vertex in vertices.chunks_exact_mut(VERTEX_SIZE) {
+            let point = self.apply(vec2(vertex[0], vertex[1]));
+            (vertex[0], vertex[1]) = (point.x, point.y);
+        }
+        vertices
+    }
+
+    // The largest factor the transform stretches lengths by in any direction
+    pub fn get_stretch(&self) -> f32 {
+        let linear = self.get_linear();
+        let sum = linear.norm_squared();
+        let determinant = linear.determinant();
+        ((sum + (sum * sum - 4.0 * determinant * determinant).max(0.0).sqrt()) / 2.0).sqrt()
+    }
+
+    // The stretch bounds how far the transform can magnify flattening error
+    pub fn get_local_tolerance(&self, tolerance: f32) -> f32 {
+        let stretch = self.get_stretch();
+        if stretch > 0.0 { tolerance / stretch } else { tolerance }
+    }
+
+    // Exact world bounds of a shape given as discs in its own coordinates. A disc maps to an ellipse, whose
+    // half-extent along each axis is the radius times the length of that row of the matrix
+    pub fn get_bounds(&self, extent: &[(Vec2, f32)]) -> Option<(Vec2, Vec2)> {
+        let linear = self.get_linear();
+        let stretch = vec2(linear.row(0).norm(), linear.row(1).norm());
+        extent.iter()
+            .map(|(center, radius)| {
+                let center = self.apply(*center);
+                (center - stretch * *radius, center + stretch * *radius)
+            })
+            .reduce(merge_bounds)
+    }
+
+    pub fn translate(&mut self, amount: Vec2) {
+        self.translation += amount;
+    }
+
+    // This transform followed by `outer`
+    pub fn then(&self, outer: &Transform) -> Transform {
+        Transform::from_matrix(outer.get_linear() * self.get_linear(), outer.apply(self.translation))
+    }
+
+    // Scales the component's own coordinates about `center`, which is also in its own coordinates
+    pub fn scale_about(&mut self, factor: Vec2, center: Vec2) {
+        self.translation += self.get_linear() * (center - center.component_mul(&factor));
+        self.scale = self.scale.component_mul(&factor);
+    }
+
+    pub fn rotate_about(&mut self, angle: f32, center: Vec2) {
+        let (sin, cos) = angle.sin_cos();
+        self.rotation += angle;
+        self.translation = Mat2::new(cos, -sin, sin, cos) * (self.translation - center) + center;
+    }
+
+    // SVG's y axis points down, so the matrix is conjugated by the same flip as `svg::point`
+    pub fn wrap_svg(&self, element: String) -> String {
+        if *self == Transform::default() {
+            return element;
+        }
+
+        let linear = self.get_linear();
+        format!("<g transform=\"matrix({} {} {} {} {} {})\">{}</g>",
+            linear[(0, 0)], -linear[(1, 0)], -linear[(0, 1)], linear[(1, 1)], self.translation.x, -self.translation.y, element)
+    }
+}
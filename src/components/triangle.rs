@@ -1,45 +1,122 @@
 use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
 
-use crate::state::State;
+use crate::svg;
 
-use super::{Component, line::Line, ComponentPreview, mouse_in_triangle};
+use super::{Component, line::Line, ComponentPreview, SavedComponent, mouse_in_triangle, add_triangle, get_path_point, find_path_position, stroke, transform::Transform, text::Label, style::Style, properties::Properties};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Triangle {
+    #[serde(flatten)]
+    properties: Properties,
     v1: Vec2,
     v2: Vec2,
     v3: Vec2,
 }
 
 impl Triangle {
-    pub fn new(vertices: Vec<Vec2>, state: &State) -> Self {
+    pub fn new(vertices: Vec<Vec2>, style: Style) -> Self {
         Self {
-            v1: state.world_space_to_screen_space(vertices[0]),
-            v2: state.world_space_to_screen_space(vertices[1]),
-            v3: state.world_space_to_screen_space(vertices[2]),
+            properties: Properties::new(style, Label::new(""), Transform::default()),
+            v1: vertices[0],
+            v2: vertices[1],
+            v3: vertices[2],
         }
     }
+
+    fn get_centroid(&self) -> Vec2 {
+        (self.v1 + self.v2 + self.v3) / 3.0
+    }
 }
 
 impl ComponentPreview for Triangle {
-    fn get_preview(vertices: Vec<Vec2>, state: &State) -> Vec<Box<dyn Component>> {
+    fn get_preview(vertices: Vec<Vec2>, style: Style) -> Vec<Box<dyn Component>> {
         match vertices.len() {
-            2 => vec![Box::new(Line::new(vertices, state))],
-            3 => vec![Box::new(Triangle::new(vertices, state))],
+            2 => vec![Box::new(Line::new(vertices, style))],
+            3 => vec![Box::new(Triangle::new(vertices, style))],
             _ => vec![],
         }
     }
 }
 
 impl Component for Triangle {
-    fn get_vertices(&self) -> Vec<f32> {
-        vec![
-            self.v1.x, self.v1.y,
-            self.v2.x, self.v2.y,
-            self.v3.x, self.v3.y,
-        ]
+    fn get_vertices(&self, _tolerance: f32) -> Vec<f32> {
+        let mut vertices = vec![];
+        if let Some(fill) = self.properties.style.get_fill(self.properties.highlighted) {
+            add_triangle(&mut vertices, self.v1, self.v2, self.v3, fill);
+        }
+        if let Some(stroke) = self.properties.style.get_stroke(self.properties.highlighted) {
+            stroke::add_stroke(&mut vertices, &[self.v1, self.v2, self.v3], true, self.properties.style.stroke_width, self.properties.style.join, stroke);
+        }
+        self.properties.label.add_vertices(&mut vertices, self.get_centroid(), self.properties.style.get_text(self.properties.highlighted));
+        self.properties.transform.apply_vertices(vertices)
     }
 
     fn hitbox_intersects(&self, mouse_pos: Vec2) -> bool {
-        mouse_in_triangle(mouse_pos, self.v1, self.v2, self.v3)
-    }    
-}
\ No newline at end of file
+        let mouse_pos = self.properties.transform.invert(mouse_pos);
+        let vertices = [self.v1, self.v2, self.v3];
+        (self.properties.style.filled && mouse_in_triangle(mouse_pos, self.v1, self.v2, self.v3))
+            || stroke::stroke_contains(&vertices, true, self.properties.style.stroke_width, mouse_pos)
+            || self.properties.label.contains(self.get_centroid(), mouse_pos)
+    }
+
+    fn get_bounds(&self) -> Option<(Vec2, Vec2)> {
+        let mut extent = self.properties.label.get_extent(self.get_centroid());
+        if self.properties.style.is_drawn() {
+            extent.extend(stroke::get_extent(&[self.v1, self.v2, self.v3], true, self.properties.style.stroke_width, self.properties.style.join));
+        }
+        self.properties.transform.get_bounds(&extent)
+    }
+
+    fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn get_properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn get_handles(&self) -> Vec<Vec2> {
+        self.properties.transform.apply_all(vec![self.v1, self.v2, self.v3])
+    }
+
+    fn move_handle(&mut self, handle: usize, position: Vec2) {
+        let position = self.properties.transform.invert(position);
+        match handle {
+            0 => self.v1 = position,
+            1 => self.v2 = position,
+            2 => self.v3 = position,
+            _ => panic!("Triangle has no handle {}", handle),
+        }
+    }
+
+    fn get_snap_points(&self) -> Vec<Vec2> {
+        self.get_handles()
+    }
+
+    fn get_anchor(&self, position: f32) -> Option<Vec2> {
+        get_path_point(&self.get_handles(), true, position)
+    }
+
+    fn find_anchor(&self, point: Vec2, tolerance: f32) -> Option<f32> {
+        find_path_position(&self.get_handles(), true, point, tolerance)
+    }
+
+    fn save(&self) -> SavedComponent {
+        SavedComponent::Triangle(self.clone())
+    }
+
+    fn restore(&mut self, saved: &SavedComponent) {
+        let SavedComponent::Triangle(triangle) = saved else {
+            panic!("Triangle can't be restored from a different kind of component");
+        };
+        let highlighted = self.properties.highlighted;
+        *self = triangle.clone();
+        self.properties.highlighted = highlighted;
+    }
+
+    fn to_svg(&self) -> String {
+        let polygon = format!("<polygon points=\"{}\"{}/>", svg::points(&[self.v1, self.v2, self.v3]), svg::style_attributes(&self.properties.style));
+        self.properties.transform.wrap_svg(self.properties.label.with_svg(polygon, self.get_centroid(), &self.properties.style))
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use nalgebra_glm::Vec2;
+use serde::{Serialize, Deserialize};
+use serde_json::{Value, json};
+
+use crate::{component_layer::ComponentLayer, components::{SavedComponent, style::Style, text::DEFAULT_FONT_SIZE}, layers::Layer, state::State};
+
+pub const DOCUMENT_VERSION: u32 = 11;
+
+// `MIGRATIONS[i]` upgrades a version `i + 1` document to version `i + 2`. Whenever a component struct changes shape,
+// bump `DOCUMENT_VERSION` and append a migration here so older files keep opening
+const MIGRATIONS: [fn(&mut Value); (DOCUMENT_VERSION - 1) as usize] = [
+    add_style,
+    add_join,
+    add_ids,
+    add_markers,
+    add_labels,
+    add_corner_radius,
+    add_transforms,
+    add_layers,
+    add_outline_positions,
+    add_group_styles,
+];
+
+pub enum DocumentError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    MissingVersion,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentError::Io(error) => write!(f, "{}", error),
+            DocumentError::Json(error) => write!(f, "{}", error),
+            DocumentError::MissingVersion => write!(f, "document has no version field"),
+            DocumentError::UnsupportedVersion(version) => write!(f, "unsupported document version {} (latest is {})", version, DOCUMENT_VERSION),
+        }
+    }
+}
+
+impl From<io::Error> for DocumentError {
+    fn from(error: io::Error) -> Self {
+        DocumentError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(error: serde_json::Error) -> Self {
+        DocumentError::Json(error)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct View {
+    translation: Vec2,
+    zoom: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    version: u32,
+    view: View,
+    layers: Vec<Layer>,
+    components: Vec<SavedComponent>,
+}
+
+impl Document {
+    pub fn new(component_layer: &ComponentLayer, state: &State) -> Self {
+        Self {
+            version: DOCUMENT_VERSION,
+            view: View { translation: state.get_translation(), zoom: state.get_zoom() },
+            layers: component_layer.get_layers().to_vec(),
+            components: component_layer.save(),
+        }
+    }
+
+    pub fn apply(self, component_layer: &mut ComponentLayer, state: &mut State) {
+        state.set_view(self.view.translation, self.view.zoom);
+        component_layer.load(self.layers, self.components);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DocumentError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, DocumentError> {
+        let mut document: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        migrate(&mut document)?;
+        Ok(serde_json::from_value(document)?)
+    }
+}
+
+// Version 1 components were drawn solid red, with lines 2 units wide
+fn add_style(document: &mut Value) {
+    let red = json!([1.0, 0.0, 0.0, 1.0]);
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        component["style"] = match component["type"].as_str() {
+            Some("Line") => json!({ "fill": red, "filled": false, "stroke": red, "stroke_width": 2.0 }),
+            _ => json!({ "fill": red, "filled": true, "stroke": red, "stroke_width": 0.0 }),
+        };
+    }
+}
+
+// Version 2 outlines were separate quads per edge; mitred joins are the closest match
+fn add_join(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        component["style"]["join"] = json!("Miter");
+    }
+}
+
+// Connectors refer to components by ID, so every component needs one
+fn add_ids(document: &mut Value) {
+    for (i, component) in document["components"].as_array_mut().into_iter().flatten().enumerate() {
+        component["id"] = json!(i + 1);
+    }
+}
+
+fn add_markers(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        let style = match component["type"].as_str() {
+            Some("Connector") => &mut component["line"]["style"],
+            _ => &mut component["style"],
+        };
+        style["start_marker"] = json!("None");
+        style["end_marker"] = json!("None");
+    }
+}
+
+fn add_labels(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        let component = match component["type"].as_str() {
+            Some("Connector") => &mut component["line"],
+            _ => component,
+        };
+        component["style"]["text"] = json!([1.0, 1.0, 1.0, 1.0]);
+        component["label"] = json!({ "text": "", "font_size": DEFAULT_FONT_SIZE, "alignment": "Center" });
+    }
+}
+
+fn add_corner_radius(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        let style = match component["type"].as_str() {
+            Some("Connector") => &mut component["line"]["style"],
+            _ => &mut component["style"],
+        };
+        style["corner_radius"] = json!(0.0);
+    }
+}
+
+fn add_transforms(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        let component = match component["type"].as_str() {
+            Some("Connector") => &mut component["line"],
+            _ => component,
+        };
+        component["transform"] = json!({ "translation": [0.0, 0.0], "rotation": 0.0, "scale": [1.0, 1.0], "skew": 0.0 });
+    }
+}
+
+// Everything starts out on a single layer
+fn add_layers(document: &mut Value) {
+    document["layers"] = json!([{ "id": 0, "name": "Layer 1", "visible": true, "locked": false }]);
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        let component = match component["type"].as_str() {
+            Some("Connector") => &mut component["line"],
+            _ => component,
+        };
+        component["layer"] = json!(0);
+    }
+}
+
+// Bindings were to the `anchor`th of a fixed set of points, with a group's points being all of its children's. They
+// are now to a position along the outline of the shape itself, and these are the positions of those points
+fn add_outline_positions(document: &mut Value) {
+    move_bindings_to_outlines(&mut document["components"]);
+}
+
+fn move_bindings_to_outlines(components: &mut Value) {
+    let anchors: HashMap<u64, Vec<(Value, f32)>> = components.as_array().into_iter().flatten()
+        .filter_map(|component| Some((component["id"].as_u64()?, get_anchor_positions(component))))
+        .collect();
+    for component in components.as_array_mut().into_iter().flatten() {
+        if let Some(children) = component.get_mut("children") {
+            move_bindings_to_outlines(children);
+        }
+        for binding in component.get_mut("bindings").and_then(Value::as_array_mut).into_iter().flatten() {
+            let anchor = binding["component"].as_u64()
+                .zip(binding["anchor"].as_u64())
+                .and_then(|(id, anchor)| anchors.get(&id)?.get(anchor as usize));
+            *binding = match anchor {
+                Some((id, position)) => json!({ "component": id, "position": position }),
+                None => Value::Null,
+            };
+        }
+    }
+}
+
+fn get_anchor_positions(component: &Value) -> Vec<(Value, f32)> {
+    let spaced = |count: usize, closed: bool| -> Vec<f32> {
+        let edges = if closed { count } else { count.saturating_sub(1) }.max(1);
+        (0..count).map(|i| i as f32 / edges as f32).collect()
+    };
+    let points = component["points"].as_array().map_or(0, Vec::len);
+    let positions = match component["type"].as_str() {
+        // Corners, then the middle of each edge
+        Some("Rect") => vec![0.0, 0.25, 0.5, 0.75, 0.125, 0.375, 0.625, 0.875],
+        Some("Circle" | "Ellipse") => spaced(4, true),
+        // The middle of each edge of the label's box, if it has any text
+        Some("Text") if component["label"]["text"].as_str().is_some_and(|text| !text.is_empty()) => vec![0.125, 0.375, 0.625, 0.875],
+        Some("Line") => vec![0.0, 1.0, 0.5],
+        Some("Triangle") => spaced(3, true),
+        Some("Polygon") => spaced(points, true),
+        Some("Polyline") => spaced(points, false),
+        // The points on the curve between its control points
+        Some("Curve") => spaced(points.div_ceil(3), false),
+        Some("Group") => {
+            return component["children"].as_array().into_iter().flatten().flat_map(get_anchor_positions).collect();
+        }
+        _ => vec![],
+    };
+    positions.into_iter().map(|position| (component["id"].clone(), position)).collect()
+}
+
+// Groups drew their labels in their first child's style, and now have one of their own
+fn add_group_styles(document: &mut Value) {
+    for component in document["components"].as_array_mut().into_iter().flatten() {
+        add_group_style(component);
+    }
+}
+
+fn add_group_style(component: &mut Value) {
+    if component["type"] != "Group" {
+        return;
+    }
+
+    let children = component.get_mut("children").and_then(Value::as_array_mut);
+    for child in children.into_iter().flatten() {
+        add_group_style(child);
+    }
+    let style = match component["children"].get(0) {
+        Some(child) if child["type"] == "Connector" => child["line"]["style"].clone(),
+        Some(child) => child["style"].clone(),
+        None => json!(Style::default()),
+    };
+    component["style"] = style;
+}
+
+pub fn migrate(document: &mut Value) -> Result<(), DocumentError> {
+    let version = document.get("version")
+        .and_then(Value::as_u64)
+        .ok_or(DocumentError::MissingVersion)? as u32;
+    if version == 0 || version > DOCUMENT_VERSION {
+        return Err(DocumentError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(document);
+    }
+    document["version"] = Value::from(DOCUMENT_VERSION);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::components::Binding;
+
+    use super::*;
+
+    const VERSION_1: &str = include_str!("../resources/tests/v1.json");
+
+    fn load_version_1() -> Document {
+        let mut document: Value = serde_json::from_str(VERSION_1).unwrap();
+        assert!(migrate(&mut document).is_ok());
+        serde_json::from_value(document).unwrap()
+    }
+
+    #[test]
+    fn version_1_documents_migrate_to_the_latest_version() {
+        let mut document: Value = serde_json::from_str(VERSION_1).unwrap();
+        assert!(migrate(&mut document).is_ok());
+        assert_eq!(document["version"], json!(DOCUMENT_VERSION));
+        assert_eq!(document["layers"].as_array().map(Vec::len), Some(1));
+
+        let ids: Vec<&Value> = document["components"].as_array().unwrap().iter().map(|component| &component["id"]).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        assert_eq!(document["components"][3]["style"]["stroke_width"], json!(2.0));
+        assert_eq!(document["components"][0]["style"]["filled"], json!(true));
+
+        let document: Document = serde_json::from_value(document).unwrap();
+        assert_eq!(document.components.len(), 4);
+    }
+
+    #[test]
+    fn anchor_indices_become_outline_positions() {
+        let connector = |bindings: Value| json!({ "type": "Connector", "line": {}, "bindings": bindings });
+        let mut document = json!({
+            "version": 9,
+            "components": [
+                { "type": "Rect", "id": 1 },
+                { "type": "Group", "id": 2, "children": [
+                    { "type": "Circle", "id": 3 },
+                    { "type": "Polyline", "id": 4, "points": [[0, 0], [1, 0], [2, 0]] },
+                    connector(json!([{ "component": 3, "anchor": 1 }, null])),
+                ] },
+                connector(json!([{ "component": 1, "anchor": 5 }, { "component": 2, "anchor": 5 }])),
+                connector(json!([{ "component": 1, "anchor": 8 }, { "component": 9, "anchor": 0 }])),
+            ],
+        });
+        assert!(migrate(&mut document).is_ok());
+
+        let components = &document["components"];
+        assert_eq!(components[2]["bindings"], json!([{ "component": 1, "position": 0.375 }, { "component": 4, "position": 0.5 }]));
+        assert_eq!(components[3]["bindings"], json!([null, null]));
+        assert_eq!(components[1]["children"][2]["bindings"], json!([{ "component": 3, "position": 0.25 }, null]));
+        assert!(components[0].get("children").is_none());
+        assert!(serde_json::from_value::<Binding>(components[2]["bindings"][0].clone()).is_ok());
+    }
+
+    #[test]
+    fn groups_take_their_first_childs_style() {
+        let style = |stroke_width: f32| json!({ "stroke_width": stroke_width });
+        let mut document = json!({
+            "version": 10,
+            "components": [
+                { "type": "Group", "id": 1, "children": [
+                    { "type": "Group", "id": 2, "children": [
+                        { "type": "Connector", "id": 3, "line": { "style": style(3.0) } },
+                    ] },
+                    { "type": "Rect", "id": 4, "style": style(4.0) },
+                ] },
+                { "type": "Group", "id": 5, "children": [] },
+            ],
+        });
+        assert!(migrate(&mut document).is_ok());
+
+        let components = &document["components"];
+        assert_eq!(components[0]["style"], style(3.0));
+        assert_eq!(components[0]["children"][0]["style"], style(3.0));
+        assert_eq!(components[1]["style"], json!(Style::default()));
+        assert!(components[0]["children"][1].get("children").is_none());
+    }
+
+    #[test]
+    fn saved_documents_load_back_unchanged() {
+        let (mut component_layer, mut state) = (ComponentLayer::new(), State::new());
+        load_version_1().apply(&mut component_layer, &mut state);
+        let saved = Document::new(&component_layer, &state);
+
+        let path = env::temp_dir().join(format!("diagrams-round-trip-{}.json", std::process::id()));
+        assert!(saved.save(&path).is_ok());
+        let loaded = Document::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let (mut component_layer, mut state) = (ComponentLayer::new(), State::new());
+        loaded.ok().unwrap().apply(&mut component_layer, &mut state);
+        let reloaded = Document::new(&component_layer, &state);
+        assert_eq!(serde_json::to_value(reloaded).unwrap(), serde_json::to_value(saved).unwrap());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in [0, DOCUMENT_VERSION + 1] {
+            let mut document = json!({ "version": version, "components": [] });
+            assert!(matches!(migrate(&mut document), Err(DocumentError::UnsupportedVersion(v)) if v == version));
+        }
+        assert!(matches!(migrate(&mut json!({})), Err(DocumentError::MissingVersion)));
+    }
+}
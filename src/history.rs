@@ -0,0 +1,243 @@
+use std::{cell::RefCell, collections::{HashSet, VecDeque}, rc::Rc};
+
+use crate::{components::{Component, ComponentId, SavedComponent}, layers::Layer};
+
+const MAX_HISTORY: usize = 256;
+
+type Primitives = Vec<Rc<RefCell<dyn Component>>>;
+
+pub enum Command {
+    Add { index: usize, component: Rc<RefCell<dyn Component>> },
+    Delete { index: usize, component: Rc<RefCell<dyn Component>> },
+    // Moves, restyles and geometry edits all snapshot the component before and after, and are restored in place so
+    // the component keeps its identity and selection
+    Modify { index: usize, before: Box<SavedComponent>, after: Box<SavedComponent> },
+    Reorder { from: usize, to: usize },
+    // Adding, renaming, reordering, hiding and locking layers all snapshot the whole list
+    Layers { before: Vec<Layer>, after: Vec<Layer> },
+    // Applies `command` to the children of the group at `index`, for edits made inside an open group
+    Nested { index: usize, id: ComponentId, command: Box<Command> },
+    Batch(Vec<Command>),
+}
+
+fn get_children_mut(group: &mut dyn Component) -> &mut Primitives {
+    group.get_children_mut().expect("Nested commands only apply to groups")
+}
+
+impl Command {
+    pub fn apply(&self, primitives: &mut Primitives, layers: &mut Vec<Layer>) {
+        match self {
+            Command::Add { index, component } => primitives.insert(*index, component.clone()),
+            Command::Delete { index, .. } => { primitives.remove(*index); },
+            Command::Modify { index, after, .. } => primitives[*index].borrow_mut().restore(after),
+            Command::Reorder { from, to } => {
+                let component = primitives.remove(*from);
+                primitives.insert(*to, component);
+            }
+            Command::Layers { after, .. } => *layers = after.clone(),
+            Command::Nested { index, command, .. } => command.apply(get_children_mut(&mut *primitives[*index].borrow_mut()), layers),
+            Command::Batch(commands) => commands.iter().for_each(|command| command.apply(primitives, layers)),
+        }
+    }
+
+    pub fn revert(&self, primitives: &mut Primitives, layers: &mut Vec<Layer>) {
+        match self {
+            Command::Add { index, .. } => { primitives.remove(*index); },
+            Command::Delete { index, component } => primitives.insert(*index, component.clone()),
+            Command::Modify { index, before, .. } => primitives[*index].borrow_mut().restore(before),
+            Command::Reorder { from, to } => {
+                let component = primitives.remove(*to);
+                primitives.insert(*from, component);
+            }
+            Command::Layers { before, .. } => *layers = before.clone(),
+            Command::Nested { index, command, .. } => command.revert(get_children_mut(&mut *primitives[*index].borrow_mut()), layers),
+            Command::Batch(commands) => commands.iter().rev().for_each(|command| command.revert(primitives, layers)),
+        }
+    }
+
+    // Components the command adds, deletes or changes, counting a group as changed when its children are
+    pub fn get_ids(&self) -> HashSet<ComponentId> {
+        match self {
+            Command::Add { component, .. } | Command::Delete { component, .. } => HashSet::from([component.borrow().get_id()]),
+            Command::Modify { before, after, .. } => HashSet::from([before.get().get_id(), after.get().get_id()]),
+            Command::Reorder { .. } | Command::Layers { .. } => HashSet::new(),
+            Command::Nested { id, .. } => HashSet::from([*id]),
+            Command::Batch(commands) => commands.iter().flat_map(Command::get_ids).collect(),
+        }
+    }
+
+    // Whether top-level components were added, deleted or reordered, which moves others to new indices
+    pub fn is_reordering(&self) -> bool {
+        match self {
+            Command::Add { .. } | Command::Delete { .. } | Command::Reorder { .. } => true,
+            Command::Modify { .. } | Command::Layers { .. } | Command::Nested { .. } => false,
+            Command::Batch(commands) => commands.iter().any(Command::is_reordering),
+        }
+    }
+
+    fn merge(&mut self, other: &Command) -> bool {
+        match (self, other) {
+            (Command::Modify { index, after, .. }, Command::Modify { index: other_index, after: other_after, .. }) if index == other_index => {
+                *after = other_after.clone();
+                true
+            }
+            (Command::Layers { after, .. }, Command::Layers { after: other_after, .. }) => {
+                *after = other_after.clone();
+                true
+            }
+            (Command::Nested { index, command, .. }, Command::Nested { index: other_index, command: other_command, .. }) if index == other_index => {
+                command.merge(other_command)
+            }
+            (Command::Batch(commands), Command::Batch(other_commands)) if commands.len() == other_commands.len() => {
+                let mergeable = commands.iter().zip(other_commands).all(|(command, other)| match (command, other) {
+                    (Command::Modify { index, .. }, Command::Modify { index: other_index, .. }) => index == other_index,
+                    _ => false,
+                });
+                mergeable && commands.iter_mut().zip(other_commands).all(|(command, other)| command.merge(other))
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct History {
+    undo: VecDeque<Command>,
+    redo: Vec<Command>,
+    sealed: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self { undo: VecDeque::new(), redo: vec![], sealed: false }
+    }
+
+    // Coalescing folds a continuous edit, such as every frame of a drag, into the previous undo step
+    pub fn push(&mut self, command: Command, coalesce: bool) {
+        self.redo.clear();
+        if coalesce && !std::mem::take(&mut self.sealed) {
+            if let Some(previous) = self.undo.back_mut() {
+                if previous.merge(&command) {
+                    return;
+                }
+            }
+        }
+
+        self.sealed = false;
+        self.undo.push_back(command);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    // Stops the next command coalescing into the current last step, for edits that only start changing things after a few frames
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    // Returns the command that was undone, if there was one
+    pub fn undo(&mut self, primitives: &mut Primitives, layers: &mut Vec<Layer>) -> Option<&Command> {
+        let command = self.undo.pop_back()?;
+        command.revert(primitives, layers);
+        self.redo.push(command);
+        self.redo.last()
+    }
+
+    pub fn redo(&mut self, primitives: &mut Primitives, layers: &mut Vec<Layer>) -> Option<&Command> {
+        let command = self.redo.pop()?;
+        command.apply(primitives, layers);
+        self.undo.push_back(command);
+        self.undo.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm::{Vec2, vec2};
+
+    use crate::components::{rect::Rect, style::Style};
+
+    use super::*;
+
+    fn add_rect(history: &mut History, primitives: &mut Primitives) -> Rc<RefCell<dyn Component>> {
+        let rect: Rc<RefCell<dyn Component>> = Rc::new(RefCell::new(Rect::new(vec![vec2(0.0, 0.0), vec2(10.0, 10.0)], Style::default())));
+        let command = Command::Add { index: primitives.len(), component: rect.clone() };
+        command.apply(primitives, &mut vec![]);
+        history.push(command, false);
+        rect
+    }
+
+    fn translate(history: &mut History, primitives: &Primitives, amount: Vec2, coalesce: bool) {
+        let command = {
+            let mut primitive = primitives[0].borrow_mut();
+            let before = Box::new(primitive.save());
+            primitive.translate(amount);
+            Command::Modify { index: 0, before, after: Box::new(primitive.save()) }
+        };
+        history.push(command, coalesce);
+    }
+
+    fn get_position(primitives: &Primitives) -> Vec2 {
+        primitives[0].borrow().get_handles()[0]
+    }
+
+    #[test]
+    fn undoing_and_redoing_a_modify_keeps_the_same_component() {
+        let (mut history, mut primitives) = (History::new(), vec![]);
+        let rect = add_rect(&mut history, &mut primitives);
+        translate(&mut history, &primitives, vec2(5.0, 0.0), false);
+
+        assert!(history.undo(&mut primitives, &mut vec![]).is_some());
+        assert!(Rc::ptr_eq(&primitives[0], &rect));
+        assert_eq!(get_position(&primitives), vec2(0.0, 0.0));
+
+        assert!(history.redo(&mut primitives, &mut vec![]).is_some());
+        assert!(Rc::ptr_eq(&primitives[0], &rect));
+        assert_eq!(get_position(&primitives), vec2(5.0, 0.0));
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_past_the_limit() {
+        let (mut history, mut primitives) = (History::new(), vec![]);
+        for _ in 0..MAX_HISTORY + 10 {
+            add_rect(&mut history, &mut primitives);
+        }
+
+        let mut undone = 0;
+        while history.undo(&mut primitives, &mut vec![]).is_some() {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_HISTORY);
+        assert_eq!(primitives.len(), 10);
+    }
+
+    #[test]
+    fn a_drag_coalesces_into_one_step() {
+        let (mut history, mut primitives) = (History::new(), vec![]);
+        add_rect(&mut history, &mut primitives);
+        translate(&mut history, &primitives, vec2(1.0, 0.0), false);
+        translate(&mut history, &primitives, vec2(1.0, 0.0), true);
+        translate(&mut history, &primitives, vec2(1.0, 0.0), true);
+
+        history.undo(&mut primitives, &mut vec![]);
+        assert_eq!(get_position(&primitives), vec2(0.0, 0.0));
+        history.redo(&mut primitives, &mut vec![]);
+        assert_eq!(get_position(&primitives), vec2(3.0, 0.0));
+    }
+
+    #[test]
+    fn sealing_starts_a_new_step() {
+        let (mut history, mut primitives) = (History::new(), vec![]);
+        add_rect(&mut history, &mut primitives);
+        translate(&mut history, &primitives, vec2(1.0, 0.0), false);
+        history.seal();
+        translate(&mut history, &primitives, vec2(1.0, 0.0), true);
+
+        history.undo(&mut primitives, &mut vec![]);
+        assert_eq!(get_position(&primitives), vec2(1.0, 0.0));
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Serialize, Deserialize};
+
+use crate::components::LayerId;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    // Locked layers are still drawn, but nothing on them can be selected
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new(id: LayerId, name: String) -> Self {
+        Self { id, name, visible: true, locked: false }
+    }
+}
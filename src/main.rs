@@ -1,9 +1,13 @@
+use std::{env, path::Path};
+
 use component_layer::ComponentLayer;
+use document::Document;
 use glutin::{event_loop::EventLoop, WindowedContext, event::{Event, WindowEvent}};
 use imgui::{FontAtlas, FontSource, FontId, FontConfig, FontGlyphRanges, Context};
 use imgui_glow_renderer::AutoRenderer;
 use imgui_winit_support::WinitPlatform;
 use nalgebra_glm::vec2;
+use rasteriser::Rasteriser;
 use state::{State, INITIAL_WINDOW_SIZE};
 use triangle_renderer::TriangleRenderer;
 
@@ -12,12 +16,19 @@ pub type Window = WindowedContext<glutin::PossiblyCurrent>;
 const MDI_RANGE: [u32; 3] = [icons::ICON_MIN as u32, icons::ICON_MAX as u32, 0];
 
 
+mod clipboard;
 mod component_layer;
+mod document;
+mod history;
 mod icons;
+mod layers;
 mod placer;
+mod rasteriser;
+mod spatial_index;
 mod components;
 mod ui;
 mod state;
+mod svg;
 mod triangle_renderer;
 mod underlay_layer;
 
@@ -44,7 +55,7 @@ fn init_winit(window: &Window, imgui_context: &mut Context) -> WinitPlatform {
 fn init_fonts(fonts: &mut FontAtlas) -> FontId {
     fonts.add_font(&[
         FontSource::TtfData { 
-            data: include_bytes!("../resources/fonts/Inter-Regular.otf"), 
+            data: components::font::FONT_DATA, 
             size_pixels: 16.0, 
             config: None,
         },
@@ -63,16 +74,49 @@ fn init_glow_context(window: &Window) -> glow::Context {
     unsafe { glow::Context::from_loader_function(|s| window.get_proc_address(s).cast()) }
 }
 
+// Unlike the document window, a failed load or save is an error the caller needs to see
+fn export_png_headless(document_path: &Path) -> Result<(), String> {
+    let mut state = State::new();
+    let mut component_storage = ComponentLayer::new();
+    Document::load(document_path)
+        .map_err(|error| format!("Failed to load '{}': {}", document_path.display(), error))?
+        .apply(&mut component_storage, &mut state);
+
+    let path = document_path.with_extension("png");
+    let mut rasteriser = Rasteriser::new(&state);
+    rasteriser.draw(&component_storage.get_vertices(state.get_tolerance()));
+    rasteriser.save_png(&path).map_err(|error| format!("Failed to export '{}': {}", path.display(), error))
+}
+
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let [_, document_path, flag] = args.as_slice() {
+        if flag == "--png" {
+            if let Err(error) = export_png_headless(Path::new(document_path)) {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     let event_loop = EventLoop::new();
     let window = init_window(&event_loop);
     let mut imgui_context = imgui::Context::create();
     let mut winit_platform = init_winit(&window, &mut imgui_context);
+    match clipboard::SystemClipboard::new() {
+        Some(clipboard) => imgui_context.set_clipboard_backend(clipboard),
+        None => eprintln!("System clipboard unavailable, copied components will only paste into this window"),
+    }
     let font = init_fonts(imgui_context.fonts());
     let mut ui_renderer = AutoRenderer::initialize(init_glow_context(&window), &mut imgui_context).expect("failed to create renderer");
     let mut triangle_renderer = TriangleRenderer::new(ui_renderer.gl_context());
     let mut state = State::new();
     let mut component_storage = ComponentLayer::new();
+    if let Some(document_path) = args.get(1) {
+        *state.get_document_path_mut() = document_path.clone();
+        state.load_document(&mut component_storage);
+    }
 
     event_loop.run(move |e, _, control_flow| {
         match e {
@@ -89,9 +133,11 @@ fn main() {
                     let ui = imgui_context.new_frame();
                     state.update_input(ui, &mut component_storage, mouse_pos);
                     let _t_font = ui.push_font(font);
-                    ui::draw(ui, &mut component_storage);
+                    ui::draw(ui, &mut component_storage, &mut state);
                     component_storage.update_io(ui, &state);
                     winit_platform.prepare_render(ui, window.window());
+                    triangle_renderer.prepare_render(&state);
+                    underlay_layer::draw(&state, &mut triangle_renderer);
                     component_storage.draw(ui, &state, &mut triangle_renderer);
                 }
                 ui_renderer.render(imgui_context.render()).expect("error rendering imgui");
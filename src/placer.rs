@@ -1,16 +1,12 @@
-trait Increment {
-    fn increment(&mut self);
-}
-
 use std::{cell::RefCell, rc::Rc};
 
 use nalgebra_glm::Vec2;
 
-use crate::{components::{ComponentType, Component}, state::State};
+use crate::components::{ComponentType, Component, style::Style};
 
 pub struct Placer {
     component_type: ComponentType,
-    vertex_count: u32,
+    vertex_count: Option<u32>,
     click_stage: u32,
     vertices: Vec<Vec2>,
 }
@@ -29,8 +25,18 @@ impl Placer {
         self.component_type
     }
 
+    // Open-ended tools are finished with `complete` instead of by reaching a vertex count
+    pub fn is_open_ended(&self) -> bool {
+        self.vertex_count.is_none()
+    }
+
+    // Part way through a fixed-count tool, rather than idle or just finished
+    pub fn is_in_progress(&self) -> bool {
+        self.vertex_count.is_some_and(|count| self.click_stage > 0 && self.click_stage < count)
+    }
+
     pub fn clicked(&mut self, world_position: Vec2) {
-        if self.click_stage == self.vertex_count {
+        if Some(self.click_stage) == self.vertex_count {
             self.click_stage = 0;
             self.vertices.clear();
         }
@@ -39,17 +45,26 @@ impl Placer {
         self.vertices.push(world_position);
     }
 
-    pub fn finish(&self, state: &State) -> Option<Rc<RefCell<dyn Component>>> {
-        if self.click_stage == self.vertex_count {
-            Some(self.component_type.build(self.vertices.clone(), state))
+    pub fn finish(&self, style: Style) -> Option<Rc<RefCell<dyn Component>>> {
+        if Some(self.click_stage) == self.vertex_count {
+            Some(self.component_type.build(self.vertices.clone(), style))
         } else {
             None
         }
     }
 
-    pub fn get_preview(&self, mouse_pos: Vec2, state: &State) -> Vec<Box<dyn Component>> {
+    pub fn complete(&mut self, style: Style) -> Option<Rc<RefCell<dyn Component>>> {
+        if !self.is_open_ended() || self.click_stage < self.component_type.get_min_vertex_count() {
+            return None;
+        }
+
+        self.click_stage = 0;
+        Some(self.component_type.build(std::mem::take(&mut self.vertices), style))
+    }
+
+    pub fn get_preview(&self, mouse_pos: Vec2, style: Style) -> Vec<Box<dyn Component>> {
         let mut preview_vertices = self.vertices.clone();
         preview_vertices.push(mouse_pos);
-        self.component_type.preview(preview_vertices, state)
+        self.component_type.preview(preview_vertices, style)
     }
 }
\ No newline at end of file
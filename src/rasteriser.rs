@@ -0,0 +1,181 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use nalgebra_glm::{Mat4, Vec4, vec4};
+
+use crate::{state::State, components::VERTEX_SIZE};
+
+// 4x4 ordered grid, matching the 16x MSAA requested by init_window
+const SAMPLES_PER_AXIS: i64 = 4;
+const SAMPLES_PER_PIXEL: usize = (SAMPLES_PER_AXIS * SAMPLES_PER_AXIS) as usize;
+// Positions are snapped to 1/256 of a pixel so edge functions are exact and shared edges are never covered twice
+const SUBPIXEL_STEPS: i64 = 256;
+const CLEAR_COLOR: [u8; 4] = [0, 0, 0, 0];
+
+type Point = (i64, i64);
+
+fn edge(from: Point, to: Point, point: Point) -> i64 {
+    (to.0 - from.0) * (point.1 - from.1) - (to.1 - from.1) * (point.0 - from.0)
+}
+
+// Samples exactly on an edge belong to the triangle only if it is a top or left edge
+fn is_top_left(from: Point, to: Point) -> bool {
+    (to.1 == from.1 && to.0 > from.0) || to.1 < from.1
+}
+
+fn covers(from: Point, to: Point, weight: i64) -> bool {
+    weight > 0 || (weight == 0 && is_top_left(from, to))
+}
+
+// Matches the blend_func_separate(SRC_ALPHA, ONE_MINUS_SRC_ALPHA, ONE, ONE_MINUS_SRC_ALPHA) used by TriangleRenderer
+fn blend(source: Vec4, destination: [u8; 4]) -> [u8; 4] {
+    let destination = vec4(destination[0] as f32, destination[1] as f32, destination[2] as f32, destination[3] as f32) / 255.0;
+    let mut result = source * source.w + destination * (1.0 - source.w);
+    result.w = source.w + destination.w * (1.0 - source.w);
+    result.map(|channel| (channel * 255.0).round().clamp(0.0, 255.0) as u8).into()
+}
+
+struct Vertex {
+    position: Point,
+    color: Vec4,
+}
+
+pub struct Rasteriser {
+    width: usize,
+    height: usize,
+    matrix: Mat4,
+    samples: Vec<[u8; 4]>,
+}
+
+impl Rasteriser {
+    pub fn new(state: &State) -> Self {
+        let width = state.get_window_size().x as usize;
+        let height = state.get_window_size().y as usize;
+        Self {
+            width,
+            height,
+            matrix: state.get_view_matrix(),
+            samples: vec![CLEAR_COLOR; width * height * SAMPLES_PER_PIXEL],
+        }
+    }
+
+    fn world_space_to_pixel_space(&self, vertex: &[f32]) -> Vertex {
+        let clip = self.matrix * vec4(vertex[0], vertex[1], 0.0, 1.0);
+        let x = (clip.x / clip.w + 1.0) * 0.5 * self.width as f32;
+        let y = (1.0 - clip.y / clip.w) * 0.5 * self.height as f32;
+        Vertex {
+            position: ((x * SUBPIXEL_STEPS as f32).round() as i64, (y * SUBPIXEL_STEPS as f32).round() as i64),
+            color: vec4(vertex[2], vertex[3], vertex[4], vertex[5]),
+        }
+    }
+
+    fn draw_triangle(&mut self, vertex1: &Vertex, vertex2: &Vertex, vertex3: &Vertex) {
+        let (vertex2, vertex3) = match edge(vertex1.position, vertex2.position, vertex3.position) {
+            0 => return,
+            area if area < 0 => (vertex3, vertex2),
+            _ => (vertex2, vertex3),
+        };
+        let (v1, v2, v3) = (vertex1.position, vertex2.position, vertex3.position);
+        let area = edge(v1, v2, v3) as f32;
+
+        let to_pixel = |coordinate: i64, limit: usize| (coordinate.div_euclid(SUBPIXEL_STEPS).max(0) as usize).min(limit);
+        let min_x = to_pixel(v1.0.min(v2.0).min(v3.0), self.width);
+        let min_y = to_pixel(v1.1.min(v2.1).min(v3.1), self.height);
+        let max_x = to_pixel(v1.0.max(v2.0).max(v3.0) + SUBPIXEL_STEPS, self.width);
+        let max_y = to_pixel(v1.1.max(v2.1).max(v3.1) + SUBPIXEL_STEPS, self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                for sample in 0..SAMPLES_PER_PIXEL as i64 {
+                    let point = (
+                        x as i64 * SUBPIXEL_STEPS + (2 * (sample % SAMPLES_PER_AXIS) + 1) * SUBPIXEL_STEPS / (2 * SAMPLES_PER_AXIS),
+                        y as i64 * SUBPIXEL_STEPS + (2 * (sample / SAMPLES_PER_AXIS) + 1) * SUBPIXEL_STEPS / (2 * SAMPLES_PER_AXIS));
+                    let w1 = edge(v2, v3, point);
+                    let w2 = edge(v3, v1, point);
+                    let w3 = edge(v1, v2, point);
+                    if covers(v2, v3, w1) && covers(v3, v1, w2) && covers(v1, v2, w3) {
+                        let color = (vertex1.color * w1 as f32 + vertex2.color * w2 as f32 + vertex3.color * w3 as f32) / area;
+                        let index = (y * self.width + x) * SAMPLES_PER_PIXEL + sample as usize;
+                        self.samples[index] = blend(color, self.samples[index]);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn draw(&mut self, vertices: &[f32]) {
+        for triangle in vertices.chunks_exact(3 * VERTEX_SIZE) {
+            let v1 = self.world_space_to_pixel_space(&triangle[..VERTEX_SIZE]);
+            let v2 = self.world_space_to_pixel_space(&triangle[VERTEX_SIZE..2 * VERTEX_SIZE]);
+            let v3 = self.world_space_to_pixel_space(&triangle[2 * VERTEX_SIZE..]);
+            self.draw_triangle(&v1, &v2, &v3);
+        }
+    }
+
+    pub fn resolve(&self) -> Vec<u8> {
+        self.samples
+            .chunks_exact(SAMPLES_PER_PIXEL)
+            .flat_map(|pixel| (0..4).map(|channel| {
+                let sum: u32 = pixel.iter().map(|sample| sample[channel] as u32).sum();
+                ((sum + SAMPLES_PER_PIXEL as u32 / 2) / SAMPLES_PER_PIXEL as u32) as u8
+            }))
+            .collect()
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.resolve())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{component_layer::ComponentLayer, document::Document};
+
+    use super::*;
+
+    // The whole view, scaled down so the test stays quick
+    const GOLDEN_SIZE: usize = 256;
+    // Rounding changes in tessellation or blending can shift a few edge samples, so only a handful of channels may be
+    // off, and then only slightly. Losing even a thin stroke changes hundreds
+    const CHANNEL_TOLERANCE: u8 = 2;
+    const MAX_DIFFERENT_CHANNELS: usize = 16;
+
+    fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    #[test]
+    fn rasterised_document_matches_the_golden_image() {
+        let resources = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/tests");
+        let (mut component_layer, mut state) = (ComponentLayer::new(), State::new());
+        let document = Document::load(&resources.join("shapes.json"));
+        document.ok().unwrap().apply(&mut component_layer, &mut state);
+
+        // The view matrix maps the view to clip space whatever the canvas size
+        let mut rasteriser = Rasteriser {
+            width: GOLDEN_SIZE,
+            height: GOLDEN_SIZE,
+            matrix: state.get_view_matrix(),
+            samples: vec![CLEAR_COLOR; GOLDEN_SIZE * GOLDEN_SIZE * SAMPLES_PER_PIXEL],
+        };
+        rasteriser.draw(&component_layer.get_vertices(state.get_tolerance()));
+        let (width, height, golden) = read_png(&resources.join("shapes.png"));
+        assert_eq!((width as usize, height as usize), (rasteriser.width, rasteriser.height));
+
+        let different = rasteriser.resolve().iter()
+            .zip(&golden)
+            .filter(|(channel, expected)| channel.abs_diff(**expected) > CHANNEL_TOLERANCE)
+            .count();
+        assert!(different <= MAX_DIFFERENT_CHANNELS, "{} channels differ from the golden image", different);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra_glm::Vec2;
+
+use crate::components::ComponentId;
+
+// World units, about the size of a typical shape at 100%
+const CELL_SIZE: f32 = 256.0;
+// Bigger components are checked by every query instead, so one huge shape doesn't fill the grid
+const MAX_CELLS_PER_COMPONENT: u64 = 64;
+
+type Cell = (i32, i32);
+
+fn get_cell(point: Vec2) -> Cell {
+    ((point.x / CELL_SIZE).floor() as i32, (point.y / CELL_SIZE).floor() as i32)
+}
+
+fn get_cells((min, max): (Vec2, Vec2)) -> impl Iterator<Item = Cell> {
+    let ((min_x, min_y), (max_x, max_y)) = (get_cell(min), get_cell(max));
+    (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+}
+
+fn get_cell_count((min, max): (Vec2, Vec2)) -> u64 {
+    let ((min_x, min_y), (max_x, max_y)) = (get_cell(min), get_cell(max));
+    let count = |min: i32, max: i32| (max as i64 - min as i64 + 1).max(0) as u64;
+    count(min_x, max_x).saturating_mul(count(min_y, max_y))
+}
+
+fn overlaps((min1, max1): (Vec2, Vec2), (min2, max2): (Vec2, Vec2)) -> bool {
+    min1.x <= max2.x && min2.x <= max1.x && min1.y <= max2.y && min2.y <= max1.y
+}
+
+// A uniform grid of component bounds, so picking, marquee selection and culling only look at what is nearby
+pub struct SpatialIndex {
+    cells: HashMap<Cell, Vec<ComponentId>>,
+    bounds: HashMap<ComponentId, (Vec2, Vec2)>,
+    oversized: HashSet<ComponentId>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self { cells: HashMap::new(), bounds: HashMap::new(), oversized: HashSet::new() }
+    }
+
+    // Replaces any earlier entry. Components that draw nothing have no bounds and are left out
+    pub fn insert(&mut self, id: ComponentId, bounds: Option<(Vec2, Vec2)>) {
+        self.remove(id);
+        let Some(bounds) = bounds else {
+            return;
+        };
+
+        if get_cell_count(bounds) > MAX_CELLS_PER_COMPONENT {
+            self.oversized.insert(id);
+        } else {
+            for cell in get_cells(bounds) {
+                self.cells.entry(cell).or_default().push(id);
+            }
+        }
+        self.bounds.insert(id, bounds);
+    }
+
+    pub fn remove(&mut self, id: ComponentId) {
+        let Some(bounds) = self.bounds.remove(&id) else {
+            return;
+        };
+        if self.oversized.remove(&id) {
+            return;
+        }
+
+        for cell in get_cells(bounds) {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|other| *other != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.bounds.clear();
+        self.oversized.clear();
+    }
+
+    // Everything whose bounds touch the rectangle. Zoomed far out, the rectangle spans more cells than are occupied,
+    // so the occupied ones are walked instead
+    pub fn query(&self, min: Vec2, max: Vec2) -> HashSet<ComponentId> {
+        let (min_cell, max_cell) = (get_cell(min), get_cell(max));
+        let candidates: Vec<&ComponentId> = if get_cell_count((min, max)) > self.cells.len() as u64 {
+            self.cells.iter()
+                .filter(|((x, y), _)| (min_cell.0..=max_cell.0).contains(x) && (min_cell.1..=max_cell.1).contains(y))
+                .flat_map(|(_, ids)| ids)
+                .collect()
+        } else {
+            get_cells((min, max)).filter_map(|cell| self.cells.get(&cell)).flatten().collect()
+        };
+        candidates.into_iter()
+            .chain(&self.oversized)
+            .filter(|id| overlaps(self.bounds[id], (min, max)))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm::vec2;
+
+    use super::*;
+
+    fn query(index: &SpatialIndex, min: (f32, f32), max: (f32, f32)) -> Vec<ComponentId> {
+        let mut ids: Vec<_> = index.query(vec2(min.0, min.1), vec2(max.0, max.1)).into_iter().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn inserted_components_are_found_where_they_are() {
+        let mut index = SpatialIndex::new();
+        index.insert(1, Some((vec2(0.0, 0.0), vec2(10.0, 10.0))));
+        index.insert(2, Some((vec2(1000.0, 1000.0), vec2(1010.0, 1010.0))));
+        index.insert(3, None);
+
+        assert_eq!(query(&index, (5.0, 5.0), (5.0, 5.0)), vec![1]);
+        assert_eq!(query(&index, (1005.0, 1005.0), (1005.0, 1005.0)), vec![2]);
+        // Same cell as 1, but outside its bounds
+        assert!(query(&index, (20.0, 20.0), (30.0, 30.0)).is_empty());
+        assert_eq!(query(&index, (0.0, 0.0), (1000.0, 1000.0)), vec![1, 2]);
+    }
+
+    #[test]
+    fn removed_components_are_not_found() {
+        let mut index = SpatialIndex::new();
+        index.insert(1, Some((vec2(0.0, 0.0), vec2(300.0, 300.0))));
+        index.remove(1);
+
+        assert!(query(&index, (0.0, 0.0), (300.0, 300.0)).is_empty());
+        assert!(index.cells.is_empty());
+        // Removing twice is harmless
+        index.remove(1);
+    }
+
+    #[test]
+    fn reinserting_a_moved_component_leaves_its_old_cells() {
+        let mut index = SpatialIndex::new();
+        index.insert(1, Some((vec2(0.0, 0.0), vec2(10.0, 10.0))));
+        index.insert(1, Some((vec2(600.0, 600.0), vec2(610.0, 610.0))));
+
+        assert!(query(&index, (5.0, 5.0), (5.0, 5.0)).is_empty());
+        assert_eq!(query(&index, (605.0, 605.0), (605.0, 605.0)), vec![1]);
+        assert_eq!(index.cells.len(), 1);
+    }
+
+    #[test]
+    fn oversized_components_are_found_anywhere_in_their_bounds() {
+        let mut index = SpatialIndex::new();
+        let size = CELL_SIZE * 20.0;
+        index.insert(1, Some((vec2(0.0, 0.0), vec2(size, size))));
+
+        assert!(index.cells.is_empty());
+        assert_eq!(query(&index, (size - 1.0, 1.0), (size - 1.0, 1.0)), vec![1]);
+        assert_eq!(query(&index, (size / 2.0, size / 2.0), (size / 2.0, size / 2.0)), vec![1]);
+        assert!(query(&index, (size + 1.0, size + 1.0), (size + 1.0, size + 1.0)).is_empty());
+
+        index.remove(1);
+        assert!(query(&index, (1.0, 1.0), (1.0, 1.0)).is_empty());
+    }
+
+    #[test]
+    fn zoomed_out_queries_walk_the_occupied_cells() {
+        let mut index = SpatialIndex::new();
+        index.insert(1, Some((vec2(0.0, 0.0), vec2(10.0, 10.0))));
+        index.insert(2, Some((vec2(-5000.0, 3000.0), vec2(-4990.0, 3010.0))));
+        index.insert(3, Some((vec2(1e6, 1e6), vec2(1e6 + 10.0, 1e6 + 10.0))));
+
+        // Far more cells than are occupied, so this takes the walk over occupied cells
+        let (min, max) = ((-1e5, -1e5), (1e5, 1e5));
+        assert!(get_cell_count((vec2(min.0, min.1), vec2(max.0, max.1))) > index.cells.len() as u64);
+        assert_eq!(query(&index, min, max), vec![1, 2]);
+        assert_eq!(query(&index, (-1e9, -1e9), (1e9, 1e9)), vec![1, 2, 3]);
+    }
+}
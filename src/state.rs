@@ -1,24 +1,46 @@
 
 
-use std::time::Instant;
+use std::{time::Instant, path::Path};
 
 use glow::HasContext;
 use glutin::dpi::PhysicalSize;
-use imgui::{MouseButton, Context, Ui};
+use imgui::{MouseButton, Context, Ui, Key};
 use imgui_glow_renderer::AutoRenderer;
-use nalgebra_glm::{Vec2, vec2};
+use nalgebra_glm::{Vec2, vec2, Mat4, vec3};
 
-use crate::component_layer::ComponentLayer;
+use crate::{component_layer::ComponentLayer, document::Document, svg, rasteriser::Rasteriser, underlay_layer};
 
 pub const INITIAL_WINDOW_SIZE: [u32; 2] = [1024, 1024];
+pub const DEFAULT_DOCUMENT_PATH: &str = "diagram.json";
 const ZOOM_SENSITIVITY: f32 = 0.15;
+// How far flattened curves may stray from their true shape on screen
+const FLATTENING_TOLERANCE_PIXELS: f32 = 0.2;
+// Releasing the mouse further than this from where it was pressed places the next vertex, so shapes can be dragged out
+const PLACEMENT_DRAG_THRESHOLD: f32 = 4.0;
+const VIEW_ANIMATION_SECONDS: f32 = 0.25;
+// Pixels left clear around framed components
+const FRAME_PADDING: f32 = 40.0;
+// Framing a single point would otherwise zoom in without limit
+const MAX_FRAME_ZOOM: f32 = 16.0;
+
+struct ViewAnimation {
+    start: Instant,
+    from: (Vec2, f32),
+    to: (Vec2, f32),
+}
 
 pub struct State {
     last_frame: Instant,
     window_size: Vec2,
     is_right_click_dragging: bool,
+    placement_press: Option<Vec2>,
     translation: Vec2,
     zoom: f32,
+    view_animation: Option<ViewAnimation>,
+    document_path: String,
+    grid_visible: bool,
+    grid_snapping: bool,
+    object_snapping: bool,
 }
 
 impl State {
@@ -27,8 +49,14 @@ impl State {
             last_frame: Instant::now(), 
             window_size: vec2(INITIAL_WINDOW_SIZE[0] as f32, INITIAL_WINDOW_SIZE[1] as f32), 
             is_right_click_dragging: false,
+            placement_press: None,
             translation: vec2(0.0, 0.0), 
-            zoom: 1.0  
+            zoom: 1.0,
+            view_animation: None,
+            document_path: DEFAULT_DOCUMENT_PATH.to_string(),
+            grid_visible: true,
+            grid_snapping: false,
+            object_snapping: true,
         }
     }
 
@@ -36,6 +64,46 @@ impl State {
         let now = Instant::now();
         imgui_context.io_mut().update_delta_time(now.duration_since(self.last_frame));
         self.last_frame = now;
+        self.update_view_animation();
+    }
+
+    // Eased, with zoom interpolated geometrically so every frame zooms by the same factor
+    fn update_view_animation(&mut self) {
+        let Some(animation) = &self.view_animation else {
+            return;
+        };
+        let t = (self.last_frame.duration_since(animation.start).as_secs_f32() / VIEW_ANIMATION_SECONDS).min(1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let ((from_translation, from_zoom), (to_translation, to_zoom)) = (animation.from, animation.to);
+        self.translation = from_translation + (to_translation - from_translation) * eased;
+        self.zoom = from_zoom * (to_zoom / from_zoom).powf(eased);
+        if t >= 1.0 {
+            self.view_animation = None;
+        }
+    }
+
+    fn animate_view(&mut self, translation: Vec2, zoom: f32) {
+        self.view_animation = Some(ViewAnimation { start: self.last_frame, from: (self.translation, self.zoom), to: (translation, zoom) });
+    }
+
+    // Centres the bounds in the window, as large as fits inside the padding
+    fn frame(&mut self, (min, max): (Vec2, Vec2)) {
+        let available = (self.window_size - vec2(2.0 * FRAME_PADDING, 2.0 * FRAME_PADDING)).map(|size| size.max(1.0));
+        let size = max - min;
+        let zoom = (2.0 * available.x / size.x).min(2.0 * available.y / size.y).min(MAX_FRAME_ZOOM);
+        self.animate_view((min + max) / 2.0, zoom);
+    }
+
+    // Frames everything drawn, or just the selection. Nothing happens when there is nothing to frame
+    pub fn frame_components(&mut self, component_storage: &ComponentLayer, selected_only: bool) {
+        if let Some(bounds) = component_storage.get_bounds(selected_only) {
+            self.frame(bounds);
+        }
+    }
+
+    // 100% and centred on the origin
+    pub fn reset_view(&mut self) {
+        self.animate_view(vec2(0.0, 0.0), 1.0);
     }
 
     pub fn update_input(&mut self, ui: &Ui, component_storage: &mut ComponentLayer, mouse_pos: Vec2) {
@@ -48,7 +116,41 @@ impl State {
         }
 
         if ui.is_mouse_clicked(MouseButton::Left) && !ui.is_any_item_hovered() && component_storage.has_placer() {
-            component_storage.click_placer(self.window_space_to_world_space(mouse_pos), self);
+            component_storage.click_placer(self.window_space_to_world_space(mouse_pos), self, ui.is_mouse_double_clicked(MouseButton::Left));
+            self.placement_press = Some(mouse_pos);
+        }
+
+        if ui.is_mouse_released(MouseButton::Left) {
+            let press = self.placement_press.take();
+            if press.is_some_and(|press| (mouse_pos - press).magnitude() > PLACEMENT_DRAG_THRESHOLD) && component_storage.is_placing() {
+                component_storage.click_placer(self.window_space_to_world_space(mouse_pos), self, false);
+            }
+        }
+
+        if ui.io().key_ctrl && ui.is_key_pressed(Key::S) {
+            self.save_document(component_storage);
+        }
+
+        if ui.io().key_ctrl && ui.is_key_pressed(Key::O) {
+            self.load_document(component_storage);
+        }
+
+        if ui.io().key_ctrl && ui.is_key_pressed(Key::E) {
+            self.export_svg(component_storage);
+        }
+
+        if !ui.io().want_text_input {
+            if ui.is_key_pressed(Key::Home) {
+                self.frame_components(component_storage, false);
+            }
+
+            if !ui.io().key_ctrl && ui.is_key_pressed(Key::F) {
+                self.frame_components(component_storage, true);
+            }
+
+            if ui.io().key_ctrl && (ui.is_key_pressed(Key::Alpha0) || ui.is_key_pressed(Key::Keypad0)) {
+                self.reset_view();
+            }
         }
 
         if self.is_right_click_dragging() {
@@ -73,7 +175,9 @@ impl State {
         }
     }
 
+    // Panning or zooming by hand takes over from any animation
     pub fn translate(&mut self, amount_world_space: Vec2) {
+        self.view_animation = None;
         self.translation -= amount_world_space / self.zoom;
     }
 
@@ -92,6 +196,90 @@ impl State {
         self.zoom
     }
 
+    pub fn set_view(&mut self, translation: Vec2, zoom: f32) {
+        self.view_animation = None;
+        self.translation = translation;
+        self.zoom = zoom;
+    }
+
+    pub fn get_window_size(&self) -> Vec2 {
+        self.window_size
+    }
+
+    pub fn get_view_matrix(&self) -> Mat4 {
+        let matrix = Mat4::identity();
+        let matrix = nalgebra_glm::scale(&matrix, &vec3(
+            self.zoom / self.window_size.x,
+            self.zoom / self.window_size.y,
+            1.0));
+        nalgebra_glm::translate(&matrix, &vec3(-self.translation.x, -self.translation.y, 0.0))
+    }
+
+    pub fn is_grid_visible(&self) -> bool {
+        self.grid_visible
+    }
+
+    pub fn toggle_grid_visible(&mut self) {
+        self.grid_visible = !self.grid_visible;
+    }
+
+    pub fn is_grid_snapping(&self) -> bool {
+        self.grid_snapping
+    }
+
+    pub fn toggle_grid_snapping(&mut self) {
+        self.grid_snapping = !self.grid_snapping;
+    }
+
+    pub fn is_object_snapping(&self) -> bool {
+        self.object_snapping
+    }
+
+    pub fn toggle_object_snapping(&mut self) {
+        self.object_snapping = !self.object_snapping;
+    }
+
+    pub fn snap(&self, point: Vec2) -> Vec2 {
+        if self.grid_snapping {
+            underlay_layer::snap_to_grid(point, self.zoom)
+        } else {
+            point
+        }
+    }
+
+    pub fn get_document_path_mut(&mut self) -> &mut String {
+        &mut self.document_path
+    }
+
+    pub fn save_document(&self, component_storage: &ComponentLayer) {
+        if let Err(error) = Document::new(component_storage, self).save(Path::new(&self.document_path)) {
+            eprintln!("Failed to save '{}': {}", self.document_path, error);
+        }
+    }
+
+    pub fn export_svg(&self, component_storage: &ComponentLayer) {
+        let path = Path::new(&self.document_path).with_extension("svg");
+        if let Err(error) = svg::save(&path, component_storage) {
+            eprintln!("Failed to export '{}': {}", path.display(), error);
+        }
+    }
+
+    pub fn export_png(&self, component_storage: &ComponentLayer) {
+        let path = Path::new(&self.document_path).with_extension("png");
+        let mut rasteriser = Rasteriser::new(self);
+        rasteriser.draw(&component_storage.get_vertices(self.get_tolerance()));
+        if let Err(error) = rasteriser.save_png(&path) {
+            eprintln!("Failed to export '{}': {}", path.display(), error);
+        }
+    }
+
+    pub fn load_document(&mut self, component_storage: &mut ComponentLayer) {
+        match Document::load(Path::new(&self.document_path)) {
+            Ok(document) => document.apply(component_storage, self),
+            Err(error) => eprintln!("Failed to load '{}': {}", self.document_path, error),
+        }
+    }
+
     pub fn window_space_to_world_space_drag(&self, coords: [f32; 2]) -> Vec2 {
         vec2(2.0 * (coords[0]), -2.0 * (coords[1]))
     }
@@ -107,8 +295,22 @@ impl State {
         vec2(self.translation.x + coords.x, self.translation.y - coords.y)
     }
 
-    pub fn world_space_to_screen_space(&self, coords: Vec2) -> Vec2 {
-        vec2(coords.x / self.window_size[0], coords.y / self.window_size[1])
+    pub fn world_space_to_window_space(&self, coords: Vec2) -> Vec2 {
+        let coords = (coords - self.translation) * self.zoom / 2.0;
+        vec2(coords.x, -coords.y) + (self.window_size / 2.0)
+    }
+
+    // The part of the world the window shows
+    pub fn get_visible_bounds(&self) -> (Vec2, Vec2) {
+        (self.window_space_to_world_space(vec2(0.0, self.window_size.y)), self.window_space_to_world_space(vec2(self.window_size.x, 0.0)))
+    }
+
+    pub fn window_space_to_world_space_distance(&self, distance: f32) -> f32 {
+        2.0 * distance / self.zoom
+    }
+
+    pub fn get_tolerance(&self) -> f32 {
+        self.window_space_to_world_space_distance(FLATTENING_TOLERANCE_PIXELS)
     }
 
     pub fn is_right_click_dragging(&self) -> bool {
@@ -0,0 +1,77 @@
+use std::{fs, io, path::Path};
+
+use nalgebra_glm::{Vec2, vec2};
+
+use crate::{component_layer::ComponentLayer, components::{stroke::MITER_LIMIT, style::{Style, Color, Join}}};
+
+const PADDING: f32 = 10.0;
+
+pub fn point(point: Vec2) -> Vec2 {
+    vec2(point.x, -point.y)
+}
+
+fn join(join: Join) -> &'static str {
+    match join {
+        Join::Miter => "miter",
+        Join::Bevel => "bevel",
+        Join::Round => "round",
+    }
+}
+
+fn color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", (color[0] * 255.0).round() as u8, (color[1] * 255.0).round() as u8, (color[2] * 255.0).round() as u8)
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn points(points: &[Vec2]) -> String {
+    points.iter()
+        .map(|vertex| point(*vertex))
+        .map(|vertex| format!("{},{}", vertex.x, vertex.y))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn fill_attributes(fill: Color) -> String {
+    format!(" fill=\"{}\" fill-opacity=\"{}\"", color(fill), fill[3])
+}
+
+pub fn stroke_attributes(style: &Style) -> String {
+    match style.get_stroke(false) {
+        Some(stroke) => format!(" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"{}\" stroke-miterlimit=\"{}\"",
+            color(stroke), stroke[3], style.stroke_width, join(style.join), MITER_LIMIT),
+        None => " stroke=\"none\"".to_string(),
+    }
+}
+
+pub fn style_attributes(style: &Style) -> String {
+    let fill = match style.get_fill(false) {
+        Some(fill) => fill_attributes(fill),
+        None => " fill=\"none\"".to_string(),
+    };
+    fill + &stroke_attributes(style)
+}
+
+pub fn export(component_layer: &ComponentLayer) -> String {
+    // Flipping y swaps which corner is the top
+    let (min, max) = component_layer.get_bounds(false)
+        .map_or((vec2(0.0, 0.0), vec2(0.0, 0.0)), |(min, max)| (point(vec2(min.x, max.y)), point(vec2(max.x, min.y))));
+    let min = min - vec2(PADDING, PADDING);
+    let size = max - min + vec2(PADDING, PADDING);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+        min.x, min.y, size.x, size.y, size.x, size.y);
+    for element in component_layer.to_svg() {
+        svg.push_str("  ");
+        svg.push_str(&element);
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn save(path: &Path, component_layer: &ComponentLayer) -> io::Result<()> {
+    fs::write(path, export(component_layer))
+}
@@ -1,7 +1,6 @@
 use std::rc::Rc;
 
-use glow::{Context, HasContext, MULTISAMPLE};
-use nalgebra_glm::{Mat4, vec3};
+use glow::{Context, HasContext, MULTISAMPLE, BLEND, SRC_ALPHA, ONE_MINUS_SRC_ALPHA, ONE};
 
 use crate::state::State;
 
@@ -19,9 +18,10 @@ pub struct TriangleRenderer {
 impl TriangleRenderer {
     pub fn new(gl: &Rc<Context>) -> Self {
         unsafe { gl.enable(MULTISAMPLE) }
-        let program = ShaderProgram::new(gl.clone(), include_str!("../resources/shaders/geometry.vert"), include_str!("../resources/shaders/geometry.frag"));
+        let program = ShaderProgram::new(gl.clone(), include_str!("../resources/shaders/geometry.vert"), include_str!("../resources/shaders/geometry.frag"), &["position", "color"]);
         let vertex_array_object = VertexArrayObject::new(gl.clone(), vec![
             VertexAttribute { index: 0, count: 2 },
+            VertexAttribute { index: 1, count: 4 },
         ]);
         TriangleRenderer { gl: gl.clone(), program, vertex_array_object }
     }
@@ -31,15 +31,13 @@ impl TriangleRenderer {
     }
 
     pub fn prepare_render(&self, state: &State) {
-        unsafe { self.gl.clear(glow::COLOR_BUFFER_BIT) };
-        let matrix = Mat4::identity();
-        let matrix = nalgebra_glm::scale(&matrix, &vec3(state.get_zoom(), state.get_zoom(), 1.0));
-        let matrix = nalgebra_glm::translate(&matrix, &vec3(
-            -state.world_space_to_screen_space(state.get_translation()).x,
-            -state.world_space_to_screen_space(state.get_translation()).y,
-            0.0));
+        unsafe {
+            self.gl.clear(glow::COLOR_BUFFER_BIT);
+            self.gl.enable(BLEND);
+            self.gl.blend_func_separate(SRC_ALPHA, ONE_MINUS_SRC_ALPHA, ONE, ONE_MINUS_SRC_ALPHA);
+        }
         self.program.use_program();
-        self.program.uniform_mat4("matrix", matrix.as_slice());
+        self.program.uniform_mat4("matrix", state.get_view_matrix().as_slice());
     }
 
     pub fn render(&self) {
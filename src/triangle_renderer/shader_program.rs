@@ -39,7 +39,7 @@ pub struct ShaderProgram {
 }
 
 impl ShaderProgram {
-    pub fn new(gl: Rc<Context>, vertex_shader_source: &str, fragment_shader_source: &str) -> Self {
+    pub fn new(gl: Rc<Context>, vertex_shader_source: &str, fragment_shader_source: &str, attributes: &[&str]) -> Self {
         let vertex_shader = Shader::new(gl.clone(), vertex_shader_source.to_string(), VERTEX_SHADER);
         let fragment_shader = Shader::new(gl.clone(), fragment_shader_source.to_string(), FRAGMENT_SHADER);
         let program = unsafe { gl.create_program().expect("Failed to create shader program") };
@@ -47,6 +47,9 @@ impl ShaderProgram {
         fragment_shader.attach(program);
 
         unsafe {
+            for (index, attribute) in attributes.iter().enumerate() {
+                gl.bind_attrib_location(program, index as u32, attribute);
+            }
             gl.link_program(program);
             assert!(gl.get_program_link_status(program), "{}", gl.get_program_info_log(program));
         }
@@ -1,11 +1,47 @@
-use imgui::{StyleColor, StyleVar, WindowFlags, Condition, Ui, ColorStackToken};
+use imgui::{Drag, StyleColor, StyleVar, WindowFlags, Condition, Ui, ColorStackToken};
 
-use crate::{icons, component_layer::ComponentLayer, components::ComponentType};
+use nalgebra_glm::{Vec2, vec2};
+
+use crate::{icons, component_layer::{Align, ComponentLayer, Distribute, ZOrder, CONTEXT_MENU}, components::{ComponentType, marker::Marker, style::{Join, Style}, text::{Alignment, Label}, transform::Transform}, state::State};
 
 const WINDOW_BG: [f32; 4] = [0.2, 0.2, 0.2, 0.7];
 const COMPONENT_BUTTON_NORMAL: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
 const COMPONENT_BUTTON_HOVER:  [f32; 4] = [0.5, 0.4, 0.2, 1.0];
 const COMPONENT_BUTTON_ACTIVE: [f32; 4] = [0.8, 0.5, 0.2, 1.0];
+const JOINS: [Join; 3] = [Join::Miter, Join::Bevel, Join::Round];
+const MARKERS: [Marker; 6] = [Marker::None, Marker::OpenArrow, Marker::FilledArrow, Marker::Diamond, Marker::Circle, Marker::Bar];
+const MARKER_NAMES: [&str; 6] = ["none", "open arrow", "filled arrow", "diamond", "circle", "bar"];
+const ALIGNMENTS: [Alignment; 3] = [Alignment::Left, Alignment::Center, Alignment::Right];
+const MAX_CORNER_RADIUS: f32 = 100.0;
+const MIN_FONT_SIZE: f32 = 4.0;
+const MAX_FONT_SIZE: f32 = 128.0;
+const MIN_SCALE: f32 = 0.01;
+const MAX_SCALE: f32 = 100.0;
+const MAX_SKEW_DEGREES: f32 = 80.0;
+
+fn is_mixed<T: PartialEq>(styles: &[Style], field: impl Fn(&Style) -> T) -> bool {
+    styles.iter().any(|style| field(style) != field(&styles[0]))
+}
+
+// The ID stays the same either way, so an edit in progress carries on once the values agree
+fn mixed_label(label: &str, mixed: bool) -> String {
+    let visible = if label.starts_with("##") { "" } else { label };
+    match (mixed, visible) {
+        (false, _) => format!("{}###{}", visible, label),
+        (true, "") => format!("mixed###{}", label),
+        (true, _) => format!("{} (mixed)###{}", visible, label),
+    }
+}
+
+fn marker_combo(ui: &Ui, label: &str, marker: &mut Marker) -> bool {
+    let mut marker_index = MARKERS.iter().position(|other| other == marker).unwrap();
+    ui.set_next_item_width(120.0);
+    if ui.combo_simple_string(label, &mut marker_index, &MARKER_NAMES) {
+        *marker = MARKERS[marker_index];
+        return true;
+    }
+    false
+}
 
 fn component_button(ui: &Ui, component_storage: &mut ComponentLayer, component_type: ComponentType, icon: char) {
     let _t_button_normal: ColorStackToken;
@@ -44,11 +80,370 @@ fn draw_components(ui: &Ui, component_storage: &mut ComponentLayer) {
             ui.same_line_with_spacing(0.0, horizontal_spacing);
             component_button(ui, component_storage, ComponentType::Circle, icons::ICON_CIRCLE_OUTLINE);
 
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            component_button(ui, component_storage, ComponentType::Ellipse, icons::ICON_ELLIPSE_OUTLINE);
+
             component_button(ui, component_storage, ComponentType::Triangle, icons::ICON_TRIANGLE_OUTLINE);
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            component_button(ui, component_storage, ComponentType::Connector, icons::ICON_RAY_START_END);
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            component_button(ui, component_storage, ComponentType::Text, icons::ICON_FORMAT_TEXT);
+
+            component_button(ui, component_storage, ComponentType::Polyline, icons::ICON_VECTOR_POLYLINE);
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            component_button(ui, component_storage, ComponentType::Polygon, icons::ICON_VECTOR_POLYGON);
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            component_button(ui, component_storage, ComponentType::Curve, icons::ICON_VECTOR_BEZIER);
+    });
+}
+
+fn draw_properties(ui: &Ui, component_storage: &mut ComponentLayer, state: &State) {
+    let _t_window_bg = ui.push_style_color(StyleColor::WindowBg, WINDOW_BG);
+    let _t_window_border_size = ui.push_style_var(StyleVar::WindowBorderSize(0.0));
+
+    ui.window("properties")
+        .flags(WindowFlags::NO_TITLE_BAR | WindowFlags::NO_RESIZE | WindowFlags::NO_MOVE | WindowFlags::ALWAYS_AUTO_RESIZE)
+        .position([200.0, 20.0], Condition::Always)
+        .build(|| {
+            // Each widget edits only its own field, so the rest of every selected component's style is kept. Fields that
+            // differ across the selection show the first component's value, marked as mixed
+            let styles = component_storage.get_styles();
+            let mut style = styles[0];
+
+            if ui.checkbox(mixed_label("##filled", is_mixed(&styles, |style| style.filled)), &mut style.filled) {
+                component_storage.edit_style(|edited| edited.filled = style.filled, !ui.is_item_activated());
+            }
+
+            ui.same_line();
+            if ui.color_edit4_config(mixed_label("fill", is_mixed(&styles, |style| style.fill)), &mut style.fill).inputs(false).alpha_bar(true).build() {
+                component_storage.edit_style(|edited| edited.fill = style.fill, !ui.is_item_activated());
+            }
+
+            if ui.color_edit4_config(mixed_label("stroke", is_mixed(&styles, |style| style.stroke)), &mut style.stroke).inputs(false).alpha_bar(true).build() {
+                component_storage.edit_style(|edited| edited.stroke = style.stroke, !ui.is_item_activated());
+            }
+
+            ui.set_next_item_width(120.0);
+            if ui.slider(mixed_label("stroke width", is_mixed(&styles, |style| style.stroke_width)), 0.0, 20.0, &mut style.stroke_width) {
+                component_storage.edit_style(|edited| edited.stroke_width = style.stroke_width, !ui.is_item_activated());
+            }
+
+            ui.set_next_item_width(120.0);
+            if ui.slider(mixed_label("corner radius", is_mixed(&styles, |style| style.corner_radius)), 0.0, MAX_CORNER_RADIUS, &mut style.corner_radius) {
+                component_storage.edit_style(|edited| edited.corner_radius = style.corner_radius, !ui.is_item_activated());
+            }
+
+            let mut join_index = JOINS.iter().position(|join| *join == style.join).unwrap();
+            ui.set_next_item_width(120.0);
+            if ui.combo_simple_string(mixed_label("join", is_mixed(&styles, |style| style.join)), &mut join_index, &["miter", "bevel", "round"]) {
+                component_storage.edit_style(|edited| edited.join = JOINS[join_index], false);
+            }
+
+            if marker_combo(ui, &mixed_label("start", is_mixed(&styles, |style| style.start_marker)), &mut style.start_marker) {
+                component_storage.edit_style(|edited| edited.start_marker = style.start_marker, false);
+            }
+
+            if marker_combo(ui, &mixed_label("end", is_mixed(&styles, |style| style.end_marker)), &mut style.end_marker) {
+                component_storage.edit_style(|edited| edited.end_marker = style.end_marker, false);
+            }
+
+            if ui.color_edit4_config(mixed_label("text", is_mixed(&styles, |style| style.text)), &mut style.text).inputs(false).alpha_bar(true).build() {
+                component_storage.edit_style(|edited| edited.text = style.text, !ui.is_item_activated());
+            }
+
+            if let Some(label) = component_storage.get_label() {
+                draw_label(ui, component_storage, label);
+            }
+
+            if let Some((pivot, transform)) = component_storage.get_transform() {
+                draw_transform(ui, component_storage, state, pivot, transform);
+            }
+    });
+}
+
+// Typing only changes the label a few frames after the box is activated, so the undo step is started on activation instead
+fn draw_label(ui: &Ui, component_storage: &mut ComponentLayer, mut label: Label) {
+    ui.separator();
+    let mut changed = ui.input_text_multiline("##label", &mut label.text, [200.0, 60.0]).build();
+    let mut activated = ui.is_item_activated();
+
+    ui.set_next_item_width(120.0);
+    changed |= ui.slider("font size", MIN_FONT_SIZE, MAX_FONT_SIZE, &mut label.font_size);
+    activated |= ui.is_item_activated();
+
+    let mut alignment_index = ALIGNMENTS.iter().position(|alignment| *alignment == label.alignment).unwrap();
+    ui.set_next_item_width(120.0);
+    if ui.combo_simple_string("alignment", &mut alignment_index, &["left", "center", "right"]) {
+        label.alignment = ALIGNMENTS[alignment_index];
+        changed = true;
+    }
+    activated |= ui.is_item_activated();
+
+    if activated {
+        component_storage.begin_edit();
+    }
+    if changed {
+        component_storage.set_label(label);
+    }
+}
+
+// Dragging only changes the value a frame after it is activated, so this starts its undo step on activation like the label
+fn draw_transform(ui: &Ui, component_storage: &mut ComponentLayer, state: &State, pivot: Vec2, mut transform: Transform) {
+    ui.separator();
+    let mut position = [pivot.x, pivot.y];
+    ui.set_next_item_width(160.0);
+    let mut changed = Drag::new("position").build_array(ui, &mut position);
+    let mut activated = ui.is_item_activated();
+
+    let mut rotation = transform.rotation.to_degrees();
+    ui.set_next_item_width(160.0);
+    if Drag::new("rotation").speed(0.5).display_format("%.1f deg").build(ui, &mut rotation) {
+        transform.rotation = rotation.to_radians();
+        changed = true;
+    }
+    activated |= ui.is_item_activated();
+
+    let mut scale = [transform.scale.x, transform.scale.y];
+    ui.set_next_item_width(160.0);
+    if Drag::new("scale").speed(0.01).range(MIN_SCALE, MAX_SCALE).build_array(ui, &mut scale) {
+        transform.scale = vec2(scale[0], scale[1]);
+        changed = true;
+    }
+    activated |= ui.is_item_activated();
+
+    let mut skew = transform.skew.to_degrees();
+    ui.set_next_item_width(160.0);
+    if ui.slider_config("skew", -MAX_SKEW_DEGREES, MAX_SKEW_DEGREES).display_format("%.1f deg").build(&mut skew) {
+        transform.skew = skew.to_radians();
+        changed = true;
+    }
+    activated |= ui.is_item_activated();
+
+    if activated {
+        component_storage.begin_edit();
+    }
+    if changed {
+        component_storage.set_transform(vec2(position[0], position[1]), transform, state);
+    }
+}
+
+fn draw_document(ui: &Ui, component_storage: &mut ComponentLayer, state: &mut State) {
+    let _t_window_bg = ui.push_style_color(StyleColor::WindowBg, WINDOW_BG);
+    let _t_button_normal= ui.push_style_color(StyleColor::Button, COMPONENT_BUTTON_NORMAL);
+    let _t_button_hovered = ui.push_style_color(StyleColor::ButtonHovered, COMPONENT_BUTTON_HOVER);
+    let _t_button_active = ui.push_style_color(StyleColor::ButtonActive, COMPONENT_BUTTON_ACTIVE);
+    let _t_window_border_size = ui.push_style_var(StyleVar::WindowBorderSize(0.0));
+
+    ui.window("document")
+        .flags(WindowFlags::NO_TITLE_BAR | WindowFlags::NO_RESIZE | WindowFlags::NO_MOVE | WindowFlags::ALWAYS_AUTO_RESIZE)
+        .position([20.0, 100.0], Condition::Always)
+        .build(|| {
+            let horizontal_spacing = 4.0;
+
+            ui.set_next_item_width(160.0);
+            ui.input_text("##path", state.get_document_path_mut()).build();
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_CONTENT_SAVE.to_string()) {
+                state.save_document(component_storage);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_FOLDER_OPEN.to_string()) {
+                state.load_document(component_storage);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_SVG.to_string()) {
+                state.export_svg(component_storage);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_FILE_PNG_BOX.to_string()) {
+                state.export_png(component_storage);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            let grid_icon = if state.is_grid_visible() { icons::ICON_GRID } else { icons::ICON_GRID_OFF };
+            if ui.button(grid_icon.to_string()) {
+                state.toggle_grid_visible();
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            let snap_icon = if state.is_grid_snapping() { icons::ICON_MAGNET_ON } else { icons::ICON_MAGNET };
+            if ui.button(snap_icon.to_string()) {
+                state.toggle_grid_snapping();
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            let object_snap_icon = if state.is_object_snapping() { icons::ICON_CROSSHAIRS_GPS } else { icons::ICON_CROSSHAIRS_OFF };
+            if ui.button(object_snap_icon.to_string()) {
+                state.toggle_object_snapping();
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_FIT_TO_SCREEN_OUTLINE.to_string()) {
+                state.frame_components(component_storage, false);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_SELECTION_SEARCH.to_string()) {
+                state.frame_components(component_storage, true);
+            }
+
+            ui.same_line_with_spacing(0.0, horizontal_spacing);
+            if ui.button(icons::ICON_MAGNIFY.to_string()) {
+                state.reset_view();
+            }
+    });
+}
+
+// Listed top layer first, the way they stack on the canvas. The active layer, which new components go on, can be
+// renamed, moved and deleted below the list
+fn draw_layers(ui: &Ui, component_storage: &mut ComponentLayer) {
+    let _t_window_bg = ui.push_style_color(StyleColor::WindowBg, WINDOW_BG);
+    let _t_window_border_size = ui.push_style_var(StyleVar::WindowBorderSize(0.0));
+
+    ui.window("layers")
+        .flags(WindowFlags::NO_TITLE_BAR | WindowFlags::NO_RESIZE | WindowFlags::NO_MOVE | WindowFlags::ALWAYS_AUTO_RESIZE)
+        .position([ui.io().display_size[0] - 20.0, 20.0], Condition::Always)
+        .position_pivot([1.0, 0.0])
+        .build(|| {
+            let mut layers = component_storage.get_layers().to_vec();
+            let active_layer = component_storage.get_active_layer();
+            let mut changed = false;
+
+            for layer in layers.iter_mut().rev() {
+                let _id = ui.push_id_usize(layer.id as usize);
+                let visibility = if layer.visible { icons::ICON_EYE } else { icons::ICON_EYE_OFF };
+                if ui.small_button(visibility.to_string()) {
+                    layer.visible = !layer.visible;
+                    changed = true;
+                }
+
+                ui.same_line();
+                let lock = if layer.locked { icons::ICON_LOCK } else { icons::ICON_LOCK_OPEN_VARIANT };
+                if ui.small_button(lock.to_string()) {
+                    layer.locked = !layer.locked;
+                    changed = true;
+                }
+
+                ui.same_line();
+                if ui.selectable_config(&layer.name).selected(layer.id == active_layer).size([120.0, 0.0]).build() {
+                    component_storage.set_active_layer(layer.id);
+                }
+            }
+
+            ui.separator();
+            let Some(index) = layers.iter().position(|layer| layer.id == active_layer) else {
+                return;
+            };
+            ui.set_next_item_width(160.0);
+            let renamed = ui.input_text("##name", &mut layers[index].name).build();
+            if ui.is_item_activated() {
+                component_storage.begin_edit();
+            }
+
+            if ui.button(icons::ICON_LAYERS_PLUS.to_string()) {
+                component_storage.add_layer();
+                return;
+            }
+
+            ui.same_line();
+            if ui.button(icons::ICON_ARROW_UP.to_string()) && index + 1 < layers.len() {
+                layers.swap(index, index + 1);
+                changed = true;
+            }
+
+            ui.same_line();
+            if ui.button(icons::ICON_ARROW_DOWN.to_string()) && index > 0 {
+                layers.swap(index, index - 1);
+                changed = true;
+            }
+
+            ui.same_line();
+            if ui.button(icons::ICON_DELETE.to_string()) {
+                component_storage.delete_layer(active_layer);
+                return;
+            }
+
+            if changed || renamed {
+                component_storage.set_layers(layers, renamed);
+            }
+    });
+}
+
+fn draw_context_menu(ui: &Ui, component_storage: &mut ComponentLayer) {
+    ui.popup(CONTEXT_MENU, || {
+        let has_selection = component_storage.has_selection();
+        let items = [
+            ("Bring to front", "Ctrl+Shift+]", ZOrder::Front),
+            ("Bring forward", "Ctrl+]", ZOrder::Forward),
+            ("Send backward", "Ctrl+[", ZOrder::Backward),
+            ("Send to back", "Ctrl+Shift+[", ZOrder::Back),
+        ];
+        for (label, shortcut, z_order) in items {
+            if ui.menu_item_config(label).shortcut(shortcut).enabled(has_selection).build() {
+                component_storage.reorder_selected(z_order);
+            }
+        }
+
+        ui.separator();
+        let selection_count = component_storage.get_selection_count();
+        if let Some(_menu) = ui.begin_menu_with_enabled("Align", selection_count >= 2) {
+            let items = [
+                ("Left", Align::Left),
+                ("Horizontal center", Align::HorizontalCenter),
+                ("Right", Align::Right),
+                ("Top", Align::Top),
+                ("Vertical center", Align::VerticalCenter),
+                ("Bottom", Align::Bottom),
+            ];
+            for (label, align) in items {
+                if ui.menu_item(label) {
+                    component_storage.align_selected(align);
+                }
+            }
+        }
+        if let Some(_menu) = ui.begin_menu_with_enabled("Distribute", selection_count >= 3) {
+            if ui.menu_item("Horizontally") {
+                component_storage.distribute_selected(Distribute::Horizontal);
+            }
+            if ui.menu_item("Vertically") {
+                component_storage.distribute_selected(Distribute::Vertical);
+            }
+        }
+
+        ui.separator();
+        if ui.menu_item_config("Group").shortcut("Ctrl+G").enabled(has_selection).build() {
+            component_storage.group_selected();
+        }
+        if ui.menu_item_config("Ungroup").shortcut("Ctrl+Shift+G").enabled(has_selection).build() {
+            component_storage.ungroup_selected();
+        }
+
+        ui.separator();
+        if let Some(_menu) = ui.begin_menu_with_enabled("Move to layer", has_selection) {
+            let layers = component_storage.get_layers().to_vec();
+            for layer in layers.iter().rev() {
+                let _id = ui.push_id_usize(layer.id as usize);
+                if ui.menu_item(&layer.name) {
+                    component_storage.move_selected_to_layer(layer.id);
+                }
+            }
+        }
     });
 }
 
-pub fn draw(ui: &Ui, component_storage: &mut ComponentLayer) {
+pub fn draw(ui: &Ui, component_storage: &mut ComponentLayer, state: &mut State) {
     ui.show_demo_window(&mut true);
     draw_components(ui, component_storage);
+    draw_properties(ui, component_storage, state);
+    draw_document(ui, component_storage, state);
+    draw_layers(ui, component_storage);
+    draw_context_menu(ui, component_storage);
 }
\ No newline at end of file
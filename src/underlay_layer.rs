@@ -0,0 +1,50 @@
+use nalgebra_glm::{Vec2, vec2};
+
+use crate::{triangle_renderer::TriangleRenderer, state::State, components::{stroke, style::{Color, Join}}};
+
+const BASE_SPACING: f32 = 10.0;
+// The grid doubles or halves its spacing to keep lines at least this far apart on screen
+const MIN_SPACING_PIXELS: f32 = 12.0;
+const MAJOR_LINE_INTERVAL: i32 = 5;
+const MINOR_LINE_COLOR: Color = [1.0, 1.0, 1.0, 0.05];
+const MAJOR_LINE_COLOR: Color = [1.0, 1.0, 1.0, 0.12];
+
+pub fn get_grid_spacing(zoom: f32) -> f32 {
+    let pixels_per_unit = zoom / 2.0;
+    let doublings = (MIN_SPACING_PIXELS / (BASE_SPACING * pixels_per_unit)).log2().ceil();
+    BASE_SPACING * 2.0_f32.powi(doublings as i32)
+}
+
+pub fn snap_to_grid(point: Vec2, zoom: f32) -> Vec2 {
+    let spacing = get_grid_spacing(zoom);
+    (point / spacing).map(f32::round) * spacing
+}
+
+fn get_vertices(state: &State) -> Vec<f32> {
+    let spacing = get_grid_spacing(state.get_zoom());
+    let width = state.window_space_to_world_space_distance(1.0);
+    let corner1 = state.window_space_to_world_space(vec2(0.0, 0.0));
+    let corner2 = state.window_space_to_world_space(state.get_window_size());
+    let min = nalgebra_glm::min2(&corner1, &corner2);
+    let max = nalgebra_glm::max2(&corner1, &corner2);
+
+    let mut vertices = vec![];
+    let mut add_line = |index: i32, from: Vec2, to: Vec2| {
+        let color = if index % MAJOR_LINE_INTERVAL == 0 { MAJOR_LINE_COLOR } else { MINOR_LINE_COLOR };
+        stroke::add_stroke(&mut vertices, &[from, to], false, width, Join::Miter, color);
+    };
+    for i in (min.x / spacing).ceil() as i32..=(max.x / spacing).floor() as i32 {
+        add_line(i, vec2(i as f32 * spacing, min.y), vec2(i as f32 * spacing, max.y));
+    }
+    for i in (min.y / spacing).ceil() as i32..=(max.y / spacing).floor() as i32 {
+        add_line(i, vec2(min.x, i as f32 * spacing), vec2(max.x, i as f32 * spacing));
+    }
+    vertices
+}
+
+pub fn draw(state: &State, triangle_renderer: &mut TriangleRenderer) {
+    if state.is_grid_visible() {
+        triangle_renderer.data(get_vertices(state));
+        triangle_renderer.render();
+    }
+}